@@ -1,4 +1,7 @@
-﻿use std::{env, path::PathBuf};
+﻿use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
 
 fn main() {
     use build_script_cfg::Cfg;
@@ -52,4 +55,105 @@ fn main() {
     bindings
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
+
+    // Error codes are defined as constants, which are not covered by the allowlist above.
+    generate_error_codes(
+        &rt_dir.join("include").join("acl"),
+        &out_path.join("error_codes.rs"),
+    );
+}
+
+/// Collects `ACL_ERROR_*` constants from the headers under `dir` into an enum.
+fn generate_error_codes(dir: &Path, out: &Path) {
+    use std::fmt::Write;
+
+    let mut codes = Vec::new();
+    collect_error_codes(dir, &mut codes);
+    // Several names may share one value, keep the first one in order of name.
+    codes.sort_unstable_by(|(a, a_code, _), (b, b_code, _)| a_code.cmp(b_code).then(a.cmp(b)));
+    codes.dedup_by_key(|(_, code, _)| *code);
+
+    let mut variants = String::new();
+    let mut from_raw = String::new();
+    let mut names = String::new();
+    let mut descriptions = String::new();
+    for (name, code, comment) in &codes {
+        if !comment.is_empty() {
+            writeln!(variants, "    #[doc = {comment:?}]").unwrap();
+        }
+        writeln!(variants, "    {name} = {code},").unwrap();
+        writeln!(from_raw, "            {code} => Some(Self::{name}),").unwrap();
+        writeln!(names, "            Self::{name} => {name:?},").unwrap();
+        writeln!(descriptions, "            Self::{name} => {comment:?},").unwrap();
+    }
+
+    let text = format!(
+        "\
+/// AscendCL 错误码，由构建脚本从头文件中提取。
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[repr(i32)]
+#[non_exhaustive]
+pub enum ErrorCode {{
+{variants}}}
+
+impl ErrorCode {{
+    /// 从错误码的值转换，未知的错误码返回 `None`。
+    pub const fn from_raw(code: aclError) -> Option<Self> {{
+        match code {{
+{from_raw}            _ => None,
+        }}
+    }}
+
+    /// 错误码在头文件中的名字。
+    pub const fn name(self) -> &'static str {{
+        match self {{
+{names}        }}
+    }}
+
+    /// 头文件中对错误码的注释，可能为空。
+    pub const fn description(self) -> &'static str {{
+        match self {{
+{descriptions}        }}
+    }}
+}}
+"
+    );
+    fs::write(out, text).expect("Couldn't write error codes!");
+}
+
+fn collect_error_codes(dir: &Path, codes: &mut Vec<(String, i32, String)>) {
+    for entry in fs::read_dir(dir).unwrap().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_error_codes(&path, codes);
+        } else if path.extension().is_some_and(|ext| ext == "h") {
+            let header = fs::read(&path).unwrap();
+            codes.extend(
+                String::from_utf8_lossy(&header)
+                    .lines()
+                    .filter_map(parse_error_code),
+            );
+        }
+    }
+}
+
+/// Parses lines like `static const int32_t ACL_ERROR_RT_PARAM_INVALID = 107000; // param invalid`
+/// or `#define ACL_ERROR_INVALID_PARAM 100000`.
+fn parse_error_code(line: &str) -> Option<(String, i32, String)> {
+    let (decl, comment) = line.split_once("//").unwrap_or((line, ""));
+    let decl = decl.trim();
+    if !decl.starts_with("static const") && !decl.starts_with("#define") {
+        return None;
+    }
+    let decl = &decl[decl.find("ACL_ERROR_")?..];
+    let (name, value) = decl.split_at(
+        decl.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(decl.len()),
+    );
+    let value = value.trim_start().trim_start_matches('=');
+    let value = value.trim().trim_end_matches(';').trim_end();
+    // `ACL_ERROR_NONE` means success.
+    let code = value.parse().ok().filter(|&code| code != 0)?;
+    Some((name.into(), code, comment.trim().into()))
 }
@@ -0,0 +1,117 @@
+﻿use crate::{AclConfig, AclError};
+use std::{error::Error, fmt, io, ptr::null, sync::Mutex};
+
+/// AscendCL 运行时的句柄。
+///
+/// 进程内第一个句柄初始化运行时，最后一个句柄释放时去初始化运行时。
+/// 设备和上下文借用句柄，因此不会在去初始化之后被使用。
+///
+/// 注意，运行时可能不支持在同一进程中去初始化之后再次初始化。
+#[derive(Debug)]
+pub struct Acl(());
+
+/// 存活的句柄数量。
+static HANDLES: Mutex<usize> = Mutex::new(0);
+
+impl Acl {
+    #[inline]
+    pub fn init() -> Self {
+        Self::try_init().unwrap()
+    }
+
+    pub fn try_init() -> Result<Self, AclError> {
+        let mut handles = HANDLES.lock().unwrap();
+        if *handles == 0 {
+            try_acl!(aclInit(null()))?
+        }
+        *handles += 1;
+        Ok(Self(()))
+    }
+
+    /// 使用 `config` 初始化运行时。
+    ///
+    /// 如果运行时已经初始化，配置不会生效。
+    #[inline]
+    pub fn init_with(config: &AclConfig) -> Self {
+        Self::try_init_with(config).unwrap()
+    }
+
+    /// 使用 `config` 初始化运行时。
+    ///
+    /// 如果运行时已经初始化，配置不会生效。
+    pub fn try_init_with(config: &AclConfig) -> Result<Self, InitError> {
+        let mut handles = HANDLES.lock().unwrap();
+        if *handles == 0 {
+            let file = config.write_temp().map_err(InitError::Config)?;
+            try_acl!(aclInit(file.c_path().as_ptr()))?
+        } else {
+            log::warn!("acl is already initialized, config is ignored")
+        }
+        *handles += 1;
+        Ok(Self(()))
+    }
+}
+
+/// 使用配置初始化运行时失败。
+#[derive(Debug)]
+pub enum InitError {
+    /// 无法将配置写入临时文件。
+    Config(io::Error),
+    /// `aclInit` 失败。
+    Acl(AclError),
+}
+
+impl From<AclError> for InitError {
+    #[inline]
+    fn from(e: AclError) -> Self {
+        Self::Acl(e)
+    }
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(e) => write!(f, "failed to write acl config file: {e}"),
+            Self::Acl(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for InitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Config(e) => Some(e),
+            Self::Acl(e) => Some(e),
+        }
+    }
+}
+
+impl Clone for Acl {
+    #[inline]
+    fn clone(&self) -> Self {
+        *HANDLES.lock().unwrap() += 1;
+        Self(())
+    }
+}
+
+impl Drop for Acl {
+    fn drop(&mut self) {
+        let mut handles = HANDLES.lock().unwrap_or_else(|e| e.into_inner());
+        *handles -= 1;
+        if *handles == 0 {
+            if let Err(e) = try_acl!(aclFinalize()) {
+                log::error!("failed to finalize: {e}")
+            }
+        }
+    }
+}
+
+#[test]
+fn test_ref_count() {
+    let a = Acl::init();
+    let b = a.clone();
+    assert!(*HANDLES.lock().unwrap() >= 2);
+    drop(a);
+    assert!(*HANDLES.lock().unwrap() >= 1);
+    drop(b)
+}
@@ -0,0 +1,140 @@
+﻿use std::{fmt, time::Duration};
+
+/// 逐次计时的性能测试结果。
+#[derive(Clone, PartialEq, Debug)]
+pub struct BenchReport {
+    /// 升序排列的每次执行时间。
+    samples: Vec<Duration>,
+}
+
+impl BenchReport {
+    /// 从每次执行的时间构造结果，没有任何执行时返回 `None`。
+    pub fn from_samples(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        Some(Self { samples })
+    }
+
+    /// 升序排列的每次执行时间。
+    #[inline]
+    pub fn samples(&self) -> &[Duration] {
+        &self.samples
+    }
+
+    #[inline]
+    pub fn min(&self) -> Duration {
+        self.samples[0]
+    }
+
+    #[inline]
+    pub fn max(&self) -> Duration {
+        self.samples[self.samples.len() - 1]
+    }
+
+    #[inline]
+    pub fn mean(&self) -> Duration {
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    #[inline]
+    pub fn median(&self) -> Duration {
+        self.percentile(50.)
+    }
+
+    #[inline]
+    pub fn p90(&self) -> Duration {
+        self.percentile(90.)
+    }
+
+    #[inline]
+    pub fn p99(&self) -> Duration {
+        self.percentile(99.)
+    }
+
+    /// 第 `p` 百分位的执行时间，按最近序数法计算。
+    pub fn percentile(&self, p: f64) -> Duration {
+        assert!((0. ..=100.).contains(&p));
+        let rank = (p / 100. * self.samples.len() as f64).ceil() as usize;
+        self.samples[rank.saturating_sub(1)]
+    }
+
+    /// 执行时间的总体标准差。
+    pub fn stddev(&self) -> Duration {
+        let mean = self.mean().as_secs_f64();
+        let var = self
+            .samples
+            .iter()
+            .map(|d| (d.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / self.samples.len() as f64;
+        Duration::from_secs_f64(var.sqrt())
+    }
+
+    /// 超出四分位距 1.5 倍范围的执行时间。
+    pub fn outliers(&self) -> Vec<Duration> {
+        let q1 = self.percentile(25.).as_secs_f64();
+        let q3 = self.percentile(75.).as_secs_f64();
+        let iqr = q3 - q1;
+        let (lo, hi) = (q1 - 1.5 * iqr, q3 + 1.5 * iqr);
+        self.samples
+            .iter()
+            .copied()
+            .filter(|d| !(lo..=hi).contains(&d.as_secs_f64()))
+            .collect()
+    }
+
+    /// 每次执行处理 `bytes` 字节时，按中位数计算的带宽（字节/秒）。
+    #[inline]
+    pub fn bytes_per_sec(&self, bytes: usize) -> f64 {
+        bytes as f64 / self.median().as_secs_f64()
+    }
+
+    /// 每次执行 `flop` 次浮点运算时，按中位数计算的算力（FLOP/s）。
+    #[inline]
+    pub fn flops(&self, flop: f64) -> f64 {
+        flop / self.median().as_secs_f64()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "n={} min={:?} median={:?} p90={:?} p99={:?} max={:?} mean={:?} stddev={:?} outliers={}",
+            self.samples.len(),
+            self.min(),
+            self.median(),
+            self.p90(),
+            self.p99(),
+            self.max(),
+            self.mean(),
+            self.stddev(),
+            self.outliers().len(),
+        )
+    }
+}
+
+#[test]
+fn test_stats() {
+    let mut samples = (1..=100).map(Duration::from_micros).collect::<Vec<_>>();
+    samples.reverse();
+    samples.push(Duration::from_millis(10));
+    let report = BenchReport::from_samples(samples).unwrap();
+
+    assert_eq!(report.min(), Duration::from_micros(1));
+    assert_eq!(report.max(), Duration::from_millis(10));
+    assert_eq!(report.median(), Duration::from_micros(51));
+    assert_eq!(report.p90(), Duration::from_micros(91));
+    assert_eq!(report.p99(), Duration::from_micros(100));
+    assert_eq!(report.outliers(), [Duration::from_millis(10)]);
+
+    assert_eq!(BenchReport::from_samples(Vec::new()), None);
+
+    let report = BenchReport::from_samples(vec![Duration::from_millis(2); 4]).unwrap();
+    assert_eq!(report.stddev(), Duration::ZERO);
+    assert_eq!(report.mean(), Duration::from_millis(2));
+    assert_eq!(report.bytes_per_sec(1 << 20), (1 << 20) as f64 * 500.);
+    assert_eq!(report.flops(1e9), 5e11);
+}
@@ -0,0 +1,184 @@
+﻿use crate::{
+    bindings::{aclrtCallbackBlockType::ACL_CALLBACK_BLOCK, aclrtContext, aclrtStream},
+    AclError, ErrorCode, Stream,
+};
+use context_spore::AsRaw;
+use std::{
+    ffi::c_void,
+    os::unix::thread::JoinHandleExt,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Mutex, PoisonError},
+    thread::{self, JoinHandle},
+};
+
+type HostFn = Box<dyn FnOnce() + Send + 'static>;
+
+impl Stream<'_> {
+    /// 在流上提交主机函数，流上之前的任务完成后由本库管理的线程调用。
+    ///
+    /// 主机函数执行完之前，流上之后的任务不会开始，因此不能在主机函数中同步这个流。
+    #[inline]
+    pub fn launch_host_fn(&self, f: impl FnOnce() + Send + 'static) {
+        self.try_launch_host_fn(f).unwrap()
+    }
+
+    /// 在流上提交主机函数，流上之前的任务完成后由本库管理的线程调用。
+    ///
+    /// 主机函数执行完之前，流上之后的任务不会开始，因此不能在主机函数中同步这个流。
+    pub fn try_launch_host_fn(&self, f: impl FnOnce() + Send + 'static) -> Result<(), AclError> {
+        let stream = unsafe { self.as_raw() };
+        subscribe(unsafe { self.ctx().as_raw() }, stream)?;
+
+        let data = Box::into_raw(Box::new(Box::new(f) as HostFn));
+        let result = try_acl!(aclrtLaunchCallback(
+            Some(call_host_fn),
+            data.cast(),
+            ACL_CALLBACK_BLOCK,
+            stream
+        ));
+        if result.is_err() {
+            drop(unsafe { Box::from_raw(data) })
+        }
+        result
+    }
+}
+
+unsafe extern "C" fn call_host_fn(data: *mut c_void) {
+    let f = unsafe { Box::from_raw(data.cast::<HostFn>()) };
+    // 不能让 panic 穿过 C 函数
+    if catch_unwind(AssertUnwindSafe(f)).is_err() {
+        log::error!("host function launched on stream panicked")
+    }
+}
+
+/// 处理一个上下文中所有流的主机函数的线程。
+struct Reporter {
+    ctx: aclrtContext,
+    thread: JoinHandle<()>,
+    streams: Vec<aclrtStream>,
+}
+
+unsafe impl Send for Reporter {}
+
+impl Reporter {
+    #[inline]
+    fn id(&self) -> u64 {
+        self.thread.as_pthread_t() as _
+    }
+
+    /// 等待处理线程退出，调用前必须已经从 `REPORTERS` 中移除并释放锁。
+    fn join(self) {
+        // 在主机函数中释放最后一个流时，处理线程无法等待自己，返回后自行退出
+        if self.thread.thread().id() != thread::current().id() && self.thread.join().is_err() {
+            log::error!("report thread panicked")
+        }
+    }
+}
+
+static REPORTERS: Mutex<Vec<Reporter>> = Mutex::new(Vec::new());
+
+/// 处理线程等待主机函数的超时时间，超时后检查是否需要退出。
+const TIMEOUT_MS: i32 = 100;
+
+fn subscribe(ctx: aclrtContext, stream: aclrtStream) -> Result<(), AclError> {
+    let mut reporters = REPORTERS.lock().unwrap();
+    let i = match reporters.iter().position(|r| r.ctx == ctx) {
+        Some(i) => i,
+        None => {
+            let ctx_ = ctx as usize;
+            let thread = thread::spawn(move || process(ctx_));
+            reporters.push(Reporter {
+                ctx,
+                thread,
+                streams: Vec::new(),
+            });
+            reporters.len() - 1
+        }
+    };
+    let reporter = &mut reporters[i];
+    if !reporter.streams.contains(&stream) {
+        if let Err(e) = try_acl!(aclrtSubscribeReport(reporter.id(), stream)) {
+            // 新建的处理线程没有订阅任何流，立即回收
+            if reporter.streams.is_empty() {
+                let reporter = reporters.swap_remove(i);
+                drop(reporters);
+                reporter.join()
+            }
+            return Err(e);
+        }
+        reporter.streams.push(stream)
+    }
+    Ok(())
+}
+
+/// 流销毁前取消订阅，最后一个流取消订阅后等待处理线程退出，
+/// 因此上下文和运行时释放时不会有线程仍在处理报告。
+pub(crate) fn unsubscribe(ctx: aclrtContext, stream: aclrtStream) {
+    let mut reporters = REPORTERS.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(i) = reporters.iter().position(|r| r.ctx == ctx) else {
+        return;
+    };
+    let reporter = &mut reporters[i];
+    let Some(j) = reporter.streams.iter().position(|&s| s == stream) else {
+        return;
+    };
+    reporter.streams.swap_remove(j);
+    if let Err(e) = try_acl!(aclrtUnSubscribeReport(reporter.id(), stream)) {
+        log::error!("failed to unsubscribe stream report: {e}")
+    }
+    if reporter.streams.is_empty() {
+        let reporter = reporters.swap_remove(i);
+        drop(reporters);
+        reporter.join()
+    }
+}
+
+fn process(ctx: usize) {
+    let ctx = ctx as aclrtContext;
+    // 等待创建线程的订阅完成
+    drop(REPORTERS.lock());
+    if let Err(e) = try_acl!(aclrtSetCurrentContext(ctx)) {
+        log::error!("report thread failed to set context: {e}")
+    }
+    loop {
+        match try_acl!(aclrtProcessReport(TIMEOUT_MS)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == Some(ErrorCode::ACL_ERROR_RT_REPORT_TIMEOUT) => {}
+            Err(e) => {
+                log::error!("failed to process report: {e}");
+                thread::sleep(std::time::Duration::from_millis(TIMEOUT_MS as _))
+            }
+        }
+        // 移除处理线程的一方负责等待线程退出
+        let reporters = REPORTERS.lock().unwrap_or_else(PoisonError::into_inner);
+        let id = thread::current().id();
+        if !reporters.iter().any(|r| r.thread.thread().id() == id) {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test() {
+    use crate::Device;
+    use std::sync::mpsc::channel;
+
+    let acl = crate::Acl::init();
+    if Device::count(&acl) == 0 {
+        return;
+    }
+    Device::new(&acl, 0).context().apply(|ctx| {
+        let stream = ctx.stream();
+        let (sender, receiver) = channel();
+        for i in 0..4 {
+            let sender = sender.clone();
+            stream.launch_host_fn(move || sender.send(i).unwrap())
+        }
+        stream.synchronize();
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        // 最后一个流释放时处理线程已经退出
+        drop(stream);
+        let ctx = unsafe { ctx.as_raw() };
+        assert!(!REPORTERS.lock().unwrap().iter().any(|r| r.ctx == ctx))
+    });
+}
@@ -0,0 +1,356 @@
+﻿use crate::json::{array, object, string};
+use std::{
+    ffi::CString,
+    fs, io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering::Relaxed},
+};
+
+/// `aclInit` 接受的 json 配置。
+///
+/// 未设置的项不会写入配置文件，由运行时使用默认值。
+/// 日志级别不属于这份配置，由环境变量 `ASCEND_GLOBAL_LOG_LEVEL` 控制。
+#[derive(Clone, Default, Debug)]
+pub struct AclConfig {
+    dump: Option<DumpConfig>,
+    profiler: Option<ProfilerConfig>,
+    err_msg_mode: Option<ErrMsgMode>,
+}
+
+/// 错误信息的获取方式。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ErrMsgMode {
+    /// 每个线程获取各自的错误信息。
+    Thread,
+    /// 获取进程内所有线程的错误信息。
+    Process,
+}
+
+/// 数据 dump 配置。
+#[derive(Clone, Debug)]
+pub struct DumpConfig {
+    path: PathBuf,
+    mode: Option<DumpMode>,
+    data: Option<DumpData>,
+    op_switch: Option<bool>,
+    op_debug: Option<bool>,
+    models: Vec<(String, Vec<String>)>,
+}
+
+/// dump 算子的输入还是输出。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DumpMode {
+    Input,
+    Output,
+    All,
+}
+
+/// dump 完整的张量还是统计信息。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DumpData {
+    Tensor,
+    Stats,
+}
+
+/// 性能数据采集配置。
+#[derive(Clone, Debug)]
+pub struct ProfilerConfig {
+    output: PathBuf,
+    switches: Vec<(&'static str, bool)>,
+    aic_metrics: Option<AicMetrics>,
+}
+
+/// AI Core 性能指标的采集项。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AicMetrics {
+    ArithmeticUtilization,
+    PipeUtilization,
+    Memory,
+    MemoryL0,
+    MemoryUB,
+    ResourceConflictRatio,
+    L2Cache,
+}
+
+impl AclConfig {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn dump(mut self, dump: DumpConfig) -> Self {
+        self.dump = Some(dump);
+        self
+    }
+
+    #[inline]
+    pub fn profiler(mut self, profiler: ProfilerConfig) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    #[inline]
+    pub fn err_msg_mode(mut self, mode: ErrMsgMode) -> Self {
+        self.err_msg_mode = Some(mode);
+        self
+    }
+
+    /// 生成 `aclInit` 接受的 json 文本。
+    pub fn to_json(&self) -> String {
+        let mut fields = Vec::new();
+        if let Some(dump) = &self.dump {
+            fields.push(("dump", dump.to_json()))
+        }
+        if let Some(profiler) = &self.profiler {
+            fields.push(("profiler", profiler.to_json()))
+        }
+        if let Some(mode) = self.err_msg_mode {
+            let mode = match mode {
+                ErrMsgMode::Thread => "0",
+                ErrMsgMode::Process => "1",
+            };
+            fields.push(("err_msg_mode", string(mode)))
+        }
+        object(fields)
+    }
+
+    /// 将配置写入临时文件，文件在返回值释放时删除。
+    pub(crate) fn write_temp(&self) -> io::Result<TempConfig> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "ascendcl-{}-{}.json",
+            process::id(),
+            COUNT.fetch_add(1, Relaxed),
+        ));
+        fs::write(&path, self.to_json())?;
+        Ok(TempConfig(path))
+    }
+}
+
+pub(crate) struct TempConfig(PathBuf);
+
+impl TempConfig {
+    #[inline]
+    pub fn c_path(&self) -> CString {
+        // 路径来自 `write_temp`，不含空字符
+        CString::new(self.0.as_os_str().as_bytes()).unwrap()
+    }
+}
+
+impl Drop for TempConfig {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl DumpConfig {
+    /// dump 数据保存到 `path`。
+    #[inline]
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            mode: None,
+            data: None,
+            op_switch: None,
+            op_debug: None,
+            models: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn mode(mut self, mode: DumpMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    #[inline]
+    pub fn data(mut self, data: DumpData) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    /// 是否 dump 单算子调用的数据。
+    #[inline]
+    pub fn op_switch(mut self, on: bool) -> Self {
+        self.op_switch = Some(on);
+        self
+    }
+
+    /// 是否开启算子溢出检测。
+    #[inline]
+    pub fn op_debug(mut self, on: bool) -> Self {
+        self.op_debug = Some(on);
+        self
+    }
+
+    /// dump 模型 `name` 中的指定层，`layers` 为空时 dump 所有层。
+    #[inline]
+    pub fn model<S: Into<String>>(
+        mut self,
+        name: impl Into<String>,
+        layers: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.models
+            .push((name.into(), layers.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    fn to_json(&self) -> String {
+        let list = self
+            .models
+            .iter()
+            .map(|(name, layers)| {
+                let mut fields = vec![("model_name", string(name))];
+                if !layers.is_empty() {
+                    fields.push(("layer", array(layers.iter().map(|l| string(l)))))
+                }
+                object(fields)
+            })
+            .collect::<Vec<_>>();
+
+        let mut fields = vec![
+            ("dump_list", array(list)),
+            ("dump_path", string(&self.path.to_string_lossy())),
+        ];
+        if let Some(mode) = self.mode {
+            let mode = match mode {
+                DumpMode::Input => "input",
+                DumpMode::Output => "output",
+                DumpMode::All => "all",
+            };
+            fields.push(("dump_mode", string(mode)))
+        }
+        if let Some(data) = self.data {
+            let data = match data {
+                DumpData::Tensor => "tensor",
+                DumpData::Stats => "stats",
+            };
+            fields.push(("dump_data", string(data)))
+        }
+        if let Some(on) = self.op_switch {
+            fields.push(("dump_op_switch", switch(on)))
+        }
+        if let Some(on) = self.op_debug {
+            fields.push(("dump_debug", switch(on)))
+        }
+        object(fields)
+    }
+}
+
+impl ProfilerConfig {
+    /// 性能数据保存到 `output`。
+    #[inline]
+    pub fn new(output: impl AsRef<Path>) -> Self {
+        Self {
+            output: output.as_ref().into(),
+            switches: Vec::new(),
+            aic_metrics: None,
+        }
+    }
+
+    /// 采集 AscendCL 接口的性能数据。
+    #[inline]
+    pub fn acl_api(self, on: bool) -> Self {
+        self.switch("acl_api", on)
+    }
+
+    /// 采集 Runtime 接口的性能数据。
+    #[inline]
+    pub fn runtime_api(self, on: bool) -> Self {
+        self.switch("runtime_api", on)
+    }
+
+    /// 采集算子下发耗时和执行耗时。
+    #[inline]
+    pub fn task_time(self, on: bool) -> Self {
+        self.switch("task_time", on)
+    }
+
+    /// 采集 AI CPU 算子的详细信息。
+    #[inline]
+    pub fn aicpu(self, on: bool) -> Self {
+        self.switch("aicpu", on)
+    }
+
+    /// 采集 L2 Cache 命中率。
+    #[inline]
+    pub fn l2(self, on: bool) -> Self {
+        self.switch("l2", on)
+    }
+
+    /// 采集通信算子的性能数据。
+    #[inline]
+    pub fn hccl(self, on: bool) -> Self {
+        self.switch("hccl", on)
+    }
+
+    #[inline]
+    pub fn aic_metrics(mut self, metrics: AicMetrics) -> Self {
+        self.aic_metrics = Some(metrics);
+        self
+    }
+
+    fn switch(mut self, key: &'static str, on: bool) -> Self {
+        self.switches.retain(|(k, _)| *k != key);
+        self.switches.push((key, on));
+        self
+    }
+
+    fn to_json(&self) -> String {
+        let mut fields = vec![
+            ("switch", switch(true)),
+            ("output", string(&self.output.to_string_lossy())),
+        ];
+        fields.extend(self.switches.iter().map(|&(key, on)| (key, switch(on))));
+        if let Some(metrics) = self.aic_metrics {
+            fields.push(("aic_metrics", string(&format!("{metrics:?}"))))
+        }
+        object(fields)
+    }
+}
+
+fn switch(on: bool) -> String {
+    string(if on { "on" } else { "off" })
+}
+
+#[test]
+fn test_empty() {
+    assert_eq!(AclConfig::new().to_json(), "{}");
+}
+
+#[test]
+fn test_dump() {
+    let config = AclConfig::new().dump(
+        DumpConfig::new("/home/output")
+            .mode(DumpMode::All)
+            .data(DumpData::Stats)
+            .op_switch(true)
+            .model("ResNet-101", ["conv1conv1_relu", "res2a_branch2a"])
+            .model("mobilenet", [] as [&str; 0]),
+    );
+    assert_eq!(
+        config.to_json(),
+        r#"{"dump":{"dump_list":[{"model_name":"ResNet-101","layer":["conv1conv1_relu","res2a_branch2a"]},{"model_name":"mobilenet"}],"dump_path":"/home/output","dump_mode":"all","dump_data":"stats","dump_op_switch":"on"}}"#
+    );
+}
+
+#[test]
+fn test_profiler() {
+    let config = AclConfig::new()
+        .profiler(
+            ProfilerConfig::new("/tmp/prof")
+                .acl_api(true)
+                .task_time(false)
+                .acl_api(false)
+                .aic_metrics(AicMetrics::PipeUtilization),
+        )
+        .err_msg_mode(ErrMsgMode::Process);
+    assert_eq!(
+        config.to_json(),
+        r#"{"profiler":{"switch":"on","output":"/tmp/prof","task_time":"off","acl_api":"off","aic_metrics":"PipeUtilization"},"err_msg_mode":"1"}"#
+    );
+}
@@ -1,77 +1,203 @@
 ﻿use crate::{
-    bindings::{aclrtContext, aclrtGetCurrentContext},
-    Device,
+    bindings::aclrtContext,
+    leak::{report_release, ResourceKind},
+    Acl, AclError, Device, ErrorCode,
 };
 use context_spore::{AsRaw, RawContainer};
 use std::{
+    cell::Cell,
+    marker::PhantomData,
     mem::{align_of, size_of},
+    num::NonZeroU32,
+    ops::Deref,
     ptr::null_mut,
+    sync::{
+        atomic::{
+            AtomicU32, AtomicU64,
+            Ordering::{Relaxed, SeqCst},
+        },
+        Mutex, PoisonError,
+    },
 };
 
 #[derive(PartialEq, Eq, Hash, Debug)]
-pub struct Context {
+pub struct Context<'acl> {
     ctx: aclrtContext,
     dev: u32,
-    primary: bool,
+    /// 默认上下文所属的代，非默认上下文为 `None`。
+    primary: Option<NonZeroU32>,
+    _acl: PhantomData<&'acl Acl>,
 }
 
-impl Device {
+impl<'acl> Device<'acl> {
     #[inline]
-    pub fn context(&self) -> Context {
+    pub fn context(&self) -> Context<'acl> {
+        self.try_context().unwrap()
+    }
+
+    pub fn try_context(&self) -> Result<Context<'acl>, AclError> {
         const { assert!(size_of::<Context>() == size_of::<[usize; 2]>()) }
         const { assert!(align_of::<Context>() == align_of::<usize>()) }
 
-        let current = get_current_ctx();
+        let current = get_current_ctx()?;
 
         let dev = unsafe { self.as_raw() };
         let mut ctx = null_mut();
-        acl!(aclrtCreateContext(&mut ctx, dev as _));
-
-        if let Some(current) = current {
-            acl!(aclrtSetCurrentContext(current));
-        }
-
-        Context {
+        // 创建失败时无法确定当前上下文
+        let epoch = EPOCH.load(SeqCst);
+        CURRENT.set(None);
+        try_acl!(aclrtCreateContext(&mut ctx, dev as _))?;
+        // 新创建的上下文成为当前上下文
+        cache_current(Some(ctx), epoch);
+        let ctx = Context {
             ctx,
             dev,
-            primary: false,
+            primary: None,
+            _acl: PhantomData,
+        };
+
+        if let Some(current) = current {
+            set_current_ctx(current)?;
         }
+        Ok(ctx)
     }
 
+    /// 获取设备的默认上下文。
+    ///
+    /// 默认上下文是引用计数的，最后一个默认上下文释放时复位设备。
     #[inline]
-    pub fn fetch_default(&self) -> Context {
-        let current = get_current_ctx();
+    pub fn fetch_default(&self) -> Context<'acl> {
+        self.try_fetch_default().unwrap()
+    }
 
+    /// 获取设备的默认上下文。
+    ///
+    /// 默认上下文是引用计数的，最后一个默认上下文释放时复位设备。
+    pub fn try_fetch_default(&self) -> Result<Context<'acl>, AclError> {
         let dev = unsafe { self.as_raw() };
-        let mut ctx = null_mut();
-        acl!(aclrtSetDevice(dev as _));
-        acl!(aclrtGetCurrentContext(&mut ctx));
+        let mut primary = PRIMARY.lock().unwrap();
+        let (ctx, generation) = match primary.iter_mut().find(|p| p.dev == dev) {
+            // 设备已经设置过，直接复用默认上下文
+            Some(p) => {
+                p.count += 1;
+                (p.ctx, p.generation)
+            }
+            None => {
+                let current = get_current_ctx()?;
 
-        if let Some(current) = current {
-            acl!(aclrtSetCurrentContext(current));
-        }
+                let mut ctx = null_mut();
+                let epoch = EPOCH.load(SeqCst);
+                CURRENT.set(None);
+                try_acl!(aclrtSetDevice(dev as _))?;
+                if let Err(e) = try_acl!(aclrtGetCurrentContext(&mut ctx)) {
+                    reset_device(dev);
+                    return Err(e);
+                }
+                // 设置设备后默认上下文成为当前上下文
+                cache_current(Some(ctx), epoch);
 
-        Context {
+                if let Some(current) = current {
+                    if let Err(e) = set_current_ctx(current) {
+                        reset_device(dev);
+                        return Err(e);
+                    }
+                }
+                // 复位后运行时可能返回相同的地址，用代区分复位前后的默认上下文
+                static GENERATION: AtomicU32 = AtomicU32::new(1);
+                let generation =
+                    NonZeroU32::new(GENERATION.fetch_add(1, Relaxed)).unwrap_or(NonZeroU32::MIN);
+                primary.push(Primary {
+                    dev,
+                    ctx,
+                    generation,
+                    count: 1,
+                });
+                (ctx, generation)
+            }
+        };
+
+        Ok(Context {
             ctx,
             dev,
-            primary: true,
-        }
+            primary: Some(generation),
+            _acl: PhantomData,
+        })
+    }
+
+    /// 复位设备，释放设备上的所有资源，用于设备故障后恢复。
+    ///
+    /// # Safety
+    ///
+    /// 复位后不能再使用设备上的任何上下文及其中创建的资源。
+    #[inline]
+    pub unsafe fn reset(&self) {
+        self.try_reset().unwrap()
+    }
+
+    /// 复位设备，释放设备上的所有资源，用于设备故障后恢复。
+    ///
+    /// # Safety
+    ///
+    /// 复位后不能再使用设备上的任何上下文及其中创建的资源。
+    pub unsafe fn try_reset(&self) -> Result<(), AclError> {
+        let dev = self.as_raw();
+        let mut primary = PRIMARY.lock().unwrap();
+        // 复位后原有的默认上下文释放时不再复位设备
+        primary.retain(|p| p.dev != dev);
+        invalidate_all_caches();
+        try_acl!(aclrtResetDevice(dev as _))
     }
 }
 
-impl Drop for Context {
+/// 已经获取的默认上下文及其引用计数。
+struct Primary {
+    dev: u32,
+    ctx: aclrtContext,
+    generation: NonZeroU32,
+    count: usize,
+}
+
+unsafe impl Send for Primary {}
+
+static PRIMARY: Mutex<Vec<Primary>> = Mutex::new(Vec::new());
+
+/// 复位设备，同时使所有线程缓存的当前上下文失效。
+fn reset_device(dev: u32) {
+    invalidate_all_caches();
+    report_release(ResourceKind::Context, try_acl!(aclrtResetDevice(dev as _)))
+}
+
+impl Drop for Context<'_> {
     #[inline]
     fn drop(&mut self) {
-        if !self.primary {
-            acl!(aclrtDestroyContext(self.ctx));
+        if let Some(generation) = self.primary {
+            let mut primary = PRIMARY.lock().unwrap_or_else(PoisonError::into_inner);
+            let Some(i) = primary
+                .iter()
+                .position(|p| p.dev == self.dev && p.generation == generation)
+            else {
+                // 所属的代已经通过 `Device::reset` 复位
+                return;
+            };
+            primary[i].count -= 1;
+            if primary[i].count == 0 {
+                primary.swap_remove(i);
+                reset_device(self.dev)
+            }
+        } else {
+            invalidate_all_caches();
+            report_release(
+                ResourceKind::Context,
+                try_acl!(aclrtDestroyContext(self.ctx)),
+            )
         }
     }
 }
 
-unsafe impl Send for Context {}
-unsafe impl Sync for Context {}
+unsafe impl Send for Context<'_> {}
+unsafe impl Sync for Context<'_> {}
 
-impl AsRaw for Context {
+impl AsRaw for Context<'_> {
     type Raw = aclrtContext;
     #[inline]
     unsafe fn as_raw(&self) -> Self::Raw {
@@ -79,39 +205,69 @@ impl AsRaw for Context {
     }
 }
 
-impl Context {
+impl<'acl> Context<'acl> {
     #[inline]
-    pub fn device(&self) -> Device {
-        Device::new(self.dev)
+    pub fn device(&self) -> Device<'acl> {
+        Device::from_index(self.dev)
     }
 
     #[inline]
     pub fn apply<T>(&self, f: impl FnOnce(&CurrentCtx) -> T) -> T {
+        f(&self.push())
+    }
+
+    /// 将上下文设置为当前上下文，守卫释放时还原原上下文。
+    ///
+    /// 即使在栈展开时释放，守卫也会还原原上下文。
+    #[inline]
+    pub fn push(&self) -> ContextGuard<'_> {
+        self.try_push().unwrap()
+    }
+
+    /// 将上下文设置为当前上下文，守卫释放时还原原上下文。
+    ///
+    /// 即使在栈展开时释放，守卫也会还原原上下文。
+    pub fn try_push(&self) -> Result<ContextGuard<'_>, AclError> {
         // 先检查当前上下文
-        match get_current_ctx() {
-            Some(current) => {
-                if current == self.ctx {
-                    // 当前上下文是目标上下文
-                    // 直接执行
-                    f(&CurrentCtx(self.ctx))
-                } else {
-                    // 当前上下文不是目标上下文
-                    // 加载目标上下文
-                    acl!(aclrtSetCurrentContext(self.ctx));
-                    // 执行依赖上下文的操作
-                    let ans = f(&CurrentCtx(self.ctx));
-                    // 原上下文非空，还原上下文
-                    acl!(aclrtSetCurrentContext(current));
-                    ans
+        let prev = get_current_ctx()?;
+        // 当前上下文不是目标上下文，加载目标上下文
+        if prev != Some(self.ctx) {
+            set_current_ctx(self.ctx)?
+        }
+        Ok(ContextGuard {
+            ctx: CurrentCtx(self.ctx),
+            prev,
+            _ctx: PhantomData,
+        })
+    }
+}
+
+/// 由 [`Context::push`] 返回的守卫，释放时还原原上下文。
+pub struct ContextGuard<'ctx> {
+    ctx: CurrentCtx,
+    prev: Option<aclrtContext>,
+    _ctx: PhantomData<&'ctx Context<'ctx>>,
+}
+
+impl Deref for ContextGuard<'_> {
+    type Target = CurrentCtx;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.ctx
+    }
+}
+
+impl Drop for ContextGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        // 原上下文为空时无法卸载上下文，保持不变
+        match self.prev {
+            Some(prev) if prev != self.ctx.0 => {
+                if let Err(e) = set_current_ctx(prev) {
+                    log::error!("failed to restore context: {e}")
                 }
             }
-            None => {
-                // 当前上下文不是目标上下文
-                // 加载目标上下文
-                acl!(aclrtSetCurrentContext(self.ctx));
-                // 执行依赖上下文的操作
-                f(&CurrentCtx(self.ctx))
-            }
+            _ => {}
         }
     }
 }
@@ -132,24 +288,53 @@ impl AsRaw for CurrentCtx {
 
 impl CurrentCtx {
     #[inline]
-    pub fn dev(&self) -> Device {
+    pub fn dev(&self) -> Device<'_> {
+        self.try_dev().unwrap()
+    }
+
+    #[inline]
+    pub fn try_dev(&self) -> Result<Device<'_>, AclError> {
         let mut dev = 0;
-        acl!(aclrtGetDevice(&mut dev));
-        Device::new(dev as _)
+        try_acl!(aclrtGetDevice(&mut dev))?;
+        Ok(Device::from_index(dev as _))
     }
 
     /// 同步上下文对应的卡上所有上下文的所有流。
     #[inline]
     pub fn sync_device(&self) {
-        acl!(aclrtSynchronizeDevice());
+        self.try_sync_device().unwrap()
+    }
+
+    /// 同步上下文对应的卡上所有上下文的所有流。
+    #[inline]
+    pub fn try_sync_device(&self) -> Result<(), AclError> {
+        try_acl!(aclrtSynchronizeDevice())
     }
 
     /// 如果存在当前上下文，在当前上下文上执行依赖上下文的操作。
     #[inline]
     pub fn apply_current<T>(f: impl FnOnce(&Self) -> T) -> Result<T, NoCtxError> {
-        get_current_ctx()
+        Self::try_apply_current(f).unwrap()
+    }
+
+    /// 如果存在当前上下文，在当前上下文上执行依赖上下文的操作。
+    ///
+    /// 查询当前上下文失败时返回外层的错误。
+    #[inline]
+    pub fn try_apply_current<T>(
+        f: impl FnOnce(&Self) -> T,
+    ) -> Result<Result<T, NoCtxError>, AclError> {
+        Ok(get_current_ctx()?
             .ok_or(NoCtxError)
-            .map(|current| f(&Self(current)))
+            .map(|current| f(&Self(current))))
+    }
+
+    /// 丢弃本线程缓存的当前上下文。
+    ///
+    /// 本库在线程局部变量中缓存当前上下文，绕过本库切换上下文后需要调用此方法。
+    #[inline]
+    pub fn invalidate_cache() {
+        CURRENT.set(None)
     }
 
     /// 直接指定当前上下文，并执行依赖上下文的操作。
@@ -184,12 +369,74 @@ impl CurrentCtx {
     }
 }
 
-fn get_current_ctx() -> Option<aclrtContext> {
+thread_local! {
+    /// 本线程的当前上下文，`None` 表示未知，需要向运行时查询。
+    static CURRENT: Cell<Option<aclrtContext>> = const { Cell::new(None) };
+    /// 缓存 `CURRENT` 时的 `EPOCH`，与 `EPOCH` 不同时缓存失效。
+    static CURRENT_EPOCH: Cell<u64> = const { Cell::new(0) };
+}
+
+/// 复位设备或销毁上下文时递增，运行时可能将释放的地址分配给新的上下文，
+/// 因此其他线程缓存的当前上下文不再可信。
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// 使所有线程缓存的当前上下文失效。
+fn invalidate_all_caches() {
+    EPOCH.fetch_add(1, SeqCst);
+}
+
+/// 缓存本线程的当前上下文，`epoch` 必须在向运行时确定当前上下文之前读取。
+fn cache_current(ctx: Option<aclrtContext>, epoch: u64) {
+    CURRENT.set(ctx);
+    CURRENT_EPOCH.set(epoch)
+}
+
+/// 本线程缓存的当前上下文，已经失效时返回 `None`。
+fn cached_ctx() -> Option<aclrtContext> {
+    CURRENT
+        .get()
+        .filter(|_| CURRENT_EPOCH.get() == EPOCH.load(SeqCst))
+}
+
+fn get_current_ctx() -> Result<Option<aclrtContext>, AclError> {
+    if let Some(current) = cached_ctx() {
+        #[cfg(debug_assertions)]
+        {
+            let actual = query_current_ctx()?;
+            assert_eq!(
+                actual,
+                Some(current),
+                "current context was switched outside ascendcl, call `CurrentCtx::invalidate_cache` after that"
+            );
+        }
+        return Ok(Some(current));
+    }
+    let epoch = EPOCH.load(SeqCst);
+    let current = query_current_ctx()?;
+    cache_current(current, epoch);
+    Ok(current)
+}
+
+fn query_current_ctx() -> Result<Option<aclrtContext>, AclError> {
     let mut current = null_mut();
-    match unsafe { aclrtGetCurrentContext(&mut current) } {
-        0 => Some(current),
-        107002 => None,
-        err => panic!("aclrtGetCurrentContext failed with error code {err}"),
+    match try_acl!(aclrtGetCurrentContext(&mut current)) {
+        Ok(()) => Ok(Some(current)),
+        Err(e) if e.kind() == Some(ErrorCode::ACL_ERROR_RT_CONTEXT_NULL) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn set_current_ctx(ctx: aclrtContext) -> Result<(), AclError> {
+    let epoch = EPOCH.load(SeqCst);
+    match try_acl!(aclrtSetCurrentContext(ctx)) {
+        Ok(()) => {
+            cache_current(Some(ctx), epoch);
+            Ok(())
+        }
+        Err(e) => {
+            CURRENT.set(None);
+            Err(e)
+        }
     }
 }
 
@@ -198,8 +445,8 @@ fn test_behavior() {
     use crate::{bindings::aclrtSetCurrentContext, Device};
     use std::ptr::null_mut;
 
-    crate::init();
-    if Device::count() == 0 {
+    let acl = crate::Acl::init();
+    if Device::count(&acl) == 0 {
         return;
     }
     let mut default = null_mut();
@@ -224,6 +471,76 @@ fn test_behavior() {
     acl!(aclrtSetDevice(0));
     acl!(aclrtGetCurrentContext(&mut current));
     assert_eq!(current, default);
+}
+
+#[test]
+fn test_guard() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let acl = crate::Acl::init();
+    if Device::count(&acl) == 0 {
+        return;
+    }
+    let dev = Device::new(&acl, 0);
+    let default = dev.fetch_default();
+    let context = dev.context();
+    default.apply(|_| {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            context.apply(|ctx| {
+                assert_eq!(unsafe { ctx.as_raw() }, unsafe { context.as_raw() });
+                panic!()
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            CurrentCtx::try_apply_current(|ctx| unsafe { ctx.as_raw() }),
+            Ok(Ok(unsafe { default.as_raw() }))
+        );
+    });
+}
+
+#[test]
+fn test_primary() {
+    let acl = crate::Acl::init();
+    if Device::count(&acl) == 0 {
+        return;
+    }
+    let count = || {
+        PRIMARY
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|p| p.dev == 0)
+            .map_or(0, |p| p.count)
+    };
+
+    let dev = Device::new(&acl, 0);
+    let before = count();
+    let a = dev.fetch_default();
+    let b = dev.fetch_default();
+    assert_eq!(unsafe { a.as_raw() }, unsafe { b.as_raw() });
+    assert_eq!(count(), before + 2);
+    drop(a);
+    b.apply(|ctx| drop(ctx.malloc::<u8>(1)));
+    drop(b);
+    let after = count();
+    assert_eq!(after, before);
+    if after == 0 {
+        // 设备已经复位，本线程不再绑定默认上下文
+        assert_eq!(cached_ctx(), None);
+        assert_eq!(query_current_ctx().unwrap(), None)
+    }
+}
+
+#[test]
+fn test_epoch() {
+    use std::{ptr::NonNull, thread};
 
-    crate::finalize()
+    let ctx = NonNull::dangling().as_ptr();
+    cache_current(Some(ctx), EPOCH.load(SeqCst));
+    assert_eq!(cached_ctx(), Some(ctx));
+    // 其他线程复位设备或销毁上下文后，本线程的缓存失效
+    thread::spawn(invalidate_all_caches).join().unwrap();
+    assert_eq!(cached_ctx(), None);
+    CURRENT.set(None)
 }
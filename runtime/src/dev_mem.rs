@@ -1,6 +1,7 @@
 ﻿use crate::{
     bindings::{aclrtMemMallocPolicy::ACL_MEM_MALLOC_HUGE_FIRST, aclrtMemcpyKind::*},
-    Blob, CurrentCtx, Stream,
+    leak::{report_release, ResourceKind},
+    AclError, Blob, Context, CurrentCtx, Stream,
 };
 use context_spore::{impl_spore, AsRaw};
 use std::{
@@ -17,10 +18,15 @@ pub struct DevByte(#[allow(unused)] u8);
 
 #[inline]
 pub fn memcpy_d2h<T: Copy>(dst: &mut [T], src: &[DevByte]) {
+    try_memcpy_d2h(dst, src).unwrap()
+}
+
+#[inline]
+pub fn try_memcpy_d2h<T: Copy>(dst: &mut [T], src: &[DevByte]) -> Result<(), AclError> {
     let len = size_of_val(dst);
     let dst = dst.as_mut_ptr().cast();
     assert_eq!(len, size_of_val(src));
-    acl!(aclrtMemcpy(
+    try_acl!(aclrtMemcpy(
         dst,
         len,
         src.as_ptr().cast(),
@@ -31,10 +37,15 @@ pub fn memcpy_d2h<T: Copy>(dst: &mut [T], src: &[DevByte]) {
 
 #[inline]
 pub fn memcpy_h2d<T: Copy>(dst: &mut [DevByte], src: &[T]) {
+    try_memcpy_h2d(dst, src).unwrap()
+}
+
+#[inline]
+pub fn try_memcpy_h2d<T: Copy>(dst: &mut [DevByte], src: &[T]) -> Result<(), AclError> {
     let len = size_of_val(src);
     let src = src.as_ptr().cast();
     assert_eq!(len, size_of_val(dst));
-    acl!(aclrtMemcpy(
+    try_acl!(aclrtMemcpy(
         dst.as_mut_ptr().cast(),
         len,
         src,
@@ -45,9 +56,14 @@ pub fn memcpy_h2d<T: Copy>(dst: &mut [DevByte], src: &[T]) {
 
 #[inline]
 pub fn memcpy_d2d(dst: &mut [DevByte], src: &[DevByte]) {
+    try_memcpy_d2d(dst, src).unwrap()
+}
+
+#[inline]
+pub fn try_memcpy_d2d(dst: &mut [DevByte], src: &[DevByte]) -> Result<(), AclError> {
     let len = size_of_val(src);
     assert_eq!(len, size_of_val(dst));
-    acl!(aclrtMemcpy(
+    try_acl!(aclrtMemcpy(
         dst.as_mut_ptr().cast(),
         len,
         src.as_ptr().cast(),
@@ -56,12 +72,65 @@ pub fn memcpy_d2d(dst: &mut [DevByte], src: &[DevByte]) {
     ))
 }
 
+/// 在不同设备的存储之间复制。
+///
+/// 如果一方已开启对另一方的访问，直接在设备间复制，否则经过锁页内存中转。
+/// 中转时每次调用都会申请并释放与 `src` 等长的锁页内存，频繁复制时应先开启对等访问。
+#[inline]
+pub fn memcpy_peer(dst_ctx: &Context, dst: &mut [DevByte], src_ctx: &Context, src: &[DevByte]) {
+    try_memcpy_peer(dst_ctx, dst, src_ctx, src).unwrap()
+}
+
+/// 在不同设备的存储之间复制。
+///
+/// 如果一方已开启对另一方的访问，直接在设备间复制，否则经过锁页内存中转。
+/// 中转时每次调用都会申请并释放与 `src` 等长的锁页内存，频繁复制时应先开启对等访问。
+pub fn try_memcpy_peer(
+    dst_ctx: &Context,
+    dst: &mut [DevByte],
+    src_ctx: &Context,
+    src: &[DevByte],
+) -> Result<(), AclError> {
+    assert_eq!(size_of_val(src), size_of_val(dst));
+    let dst_dev = dst_ctx.device();
+    let src_dev = src_ctx.device();
+    if src_ctx.is_peer_access_enabled(&dst_dev) {
+        src_ctx.apply(|_| try_memcpy_d2d(dst, src))
+    } else if dst_ctx.is_peer_access_enabled(&src_dev) {
+        dst_ctx.apply(|_| try_memcpy_d2d(dst, src))
+    } else {
+        log::debug!(
+            "peer access between {src_dev:?} and {dst_dev:?} is not enabled, copy through host"
+        );
+        try_memcpy_staged(dst_ctx, dst, src_ctx, src)
+    }
+}
+
+/// 经过锁页内存中转复制。
+fn try_memcpy_staged(
+    dst_ctx: &Context,
+    dst: &mut [DevByte],
+    src_ctx: &Context,
+    src: &[DevByte],
+) -> Result<(), AclError> {
+    src_ctx.apply(|ctx| {
+        let mut host = ctx.try_malloc_host::<u8>(src.len())?;
+        try_memcpy_d2h(&mut host, src)?;
+        dst_ctx.apply(|_| try_memcpy_h2d(dst, &host))
+    })
+}
+
 impl Stream<'_> {
     #[inline]
     pub fn memcpy_h2d<T: Copy>(&self, dst: &mut [DevByte], src: &[T]) {
+        self.try_memcpy_h2d(dst, src).unwrap()
+    }
+
+    #[inline]
+    pub fn try_memcpy_h2d<T: Copy>(&self, dst: &mut [DevByte], src: &[T]) -> Result<(), AclError> {
         let len = size_of_val(src);
         assert_eq!(len, size_of_val(dst));
-        acl!(aclrtMemcpyAsync(
+        try_acl!(aclrtMemcpyAsync(
             dst.as_mut_ptr().cast(),
             len,
             src.as_ptr().cast(),
@@ -73,9 +142,14 @@ impl Stream<'_> {
 
     #[inline]
     pub fn memcpy_d2d(&self, dst: &mut [DevByte], src: &[DevByte]) {
+        self.try_memcpy_d2d(dst, src).unwrap()
+    }
+
+    #[inline]
+    pub fn try_memcpy_d2d(&self, dst: &mut [DevByte], src: &[DevByte]) -> Result<(), AclError> {
         let len = size_of_val(src);
         assert_eq!(len, size_of_val(dst));
-        acl!(aclrtMemcpyAsync(
+        try_acl!(aclrtMemcpyAsync(
             dst.as_mut_ptr().cast(),
             len,
             src.as_ptr().cast(),
@@ -90,26 +164,34 @@ impl_spore!(DevMem and DevMemSpore by (CurrentCtx, Blob<*mut c_void>));
 
 impl CurrentCtx {
     pub fn malloc<T: Copy>(&self, len: usize) -> DevMem<'_> {
+        self.try_malloc::<T>(len).unwrap()
+    }
+
+    pub fn try_malloc<T: Copy>(&self, len: usize) -> Result<DevMem<'_>, AclError> {
         let len = Layout::array::<T>(len).unwrap().size();
         let mut ptr = null_mut();
         // NOTICE 8.0 只有 ACL_MEM_MALLOC_HUGE_FIRST 有效
-        acl!(aclrtMalloc(&mut ptr, len, ACL_MEM_MALLOC_HUGE_FIRST));
-        DevMem(unsafe { self.wrap_raw(Blob { ptr, len }) }, PhantomData)
+        try_acl!(aclrtMalloc(&mut ptr, len, ACL_MEM_MALLOC_HUGE_FIRST))?;
+        Ok(DevMem(
+            unsafe { self.wrap_raw(Blob { ptr, len }) },
+            PhantomData,
+        ))
     }
 
     pub fn from_host<T: Copy>(&self, slice: &[T]) -> DevMem<'_> {
-        let len = size_of_val(slice);
-        let src = slice.as_ptr().cast();
-        let mut ptr = null_mut();
-        acl!(aclrtMalloc(&mut ptr, len, ACL_MEM_MALLOC_HUGE_FIRST));
-        acl!(aclrtMemcpy(ptr, len, src, len, ACL_MEMCPY_HOST_TO_DEVICE));
-        DevMem(unsafe { self.wrap_raw(Blob { ptr, len }) }, PhantomData)
+        self.try_from_host(slice).unwrap()
+    }
+
+    pub fn try_from_host<T: Copy>(&self, slice: &[T]) -> Result<DevMem<'_>, AclError> {
+        let mut dev = self.try_malloc::<T>(slice.len())?;
+        try_memcpy_h2d(&mut dev, slice)?;
+        Ok(dev)
     }
 }
 
 impl Drop for DevMem<'_> {
     fn drop(&mut self) {
-        acl!(aclrtFree(self.0.rss.ptr));
+        report_release(ResourceKind::DevMem, try_acl!(aclrtFree(self.0.rss.ptr)))
     }
 }
 
@@ -155,3 +237,59 @@ impl DevMemSpore {
         self.0.rss.len == 0
     }
 }
+
+#[test]
+fn test_memcpy_peer() {
+    use crate::Device;
+
+    let acl = crate::Acl::init();
+    let n = Device::count(&acl);
+    if n == 0 {
+        return;
+    }
+    #[derive(Clone, Copy, PartialEq)]
+    enum Path {
+        Staged,
+        Auto,
+        Peer,
+    }
+
+    let data = (0..1024u32).collect::<Vec<_>>();
+    let check = |dst_dev: u32, path: Path| {
+        let src_dev = Device::new(&acl, 0);
+        let src_ctx = src_dev.context();
+        let dst_ctx = Device::new(&acl, dst_dev).context();
+        if path == Path::Peer {
+            dst_ctx.enable_peer_access(&src_dev);
+            assert!(dst_ctx.is_peer_access_enabled(&src_dev))
+        }
+        src_ctx.apply(|src| {
+            let src_mem = src.from_host(&data);
+            dst_ctx.apply(|dst| {
+                let mut dst_mem = dst.malloc::<u32>(data.len());
+                if path == Path::Staged {
+                    try_memcpy_staged(&dst_ctx, &mut dst_mem, &src_ctx, &src_mem).unwrap()
+                } else {
+                    memcpy_peer(&dst_ctx, &mut dst_mem, &src_ctx, &src_mem)
+                }
+                let mut host = vec![0u32; data.len()];
+                memcpy_d2h(&mut host, &dst_mem);
+                assert_eq!(host, data)
+            })
+        });
+        if path == Path::Peer {
+            dst_ctx.disable_peer_access(&src_dev)
+        }
+    };
+    // 同一设备上的两个上下文直接复制
+    check(0, Path::Auto);
+    if n >= 2 {
+        // 两个设备之间经过主机中转
+        check(1, Path::Staged);
+        check(1, Path::Auto);
+        // 开启对等访问后在设备间直接复制
+        if Device::new(&acl, 1).can_access_peer(&Device::new(&acl, 0)) {
+            check(1, Path::Peer)
+        }
+    }
+}
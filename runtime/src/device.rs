@@ -1,11 +1,42 @@
-﻿use crate::bindings::aclDeviceInfo::{self, *};
+﻿use crate::bindings::{
+    aclDeviceInfo::{self, *},
+    aclrtMemAttr::{self, *},
+};
+use crate::{Acl, AclError, CurrentCtx};
 use context_spore::AsRaw;
-use std::{ffi::CStr, fmt};
+use std::{ffi::CStr, fmt, marker::PhantomData, ops::Range, sync::Mutex};
 
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
-pub struct Device(u32);
+pub struct Device<'acl>(u32, PhantomData<&'acl Acl>);
 
-impl AsRaw for Device {
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NoDevError;
+
+/// 构造设备失败。
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum NewDevError {
+    /// 序号超出设备数量。
+    NoDev(NoDevError),
+    /// 查询设备数量失败。
+    Acl(AclError),
+}
+
+impl From<NoDevError> for NewDevError {
+    #[inline]
+    fn from(e: NoDevError) -> Self {
+        Self::NoDev(e)
+    }
+}
+
+impl From<AclError> for NewDevError {
+    #[inline]
+    fn from(e: AclError) -> Self {
+        Self::Acl(e)
+    }
+}
+
+impl AsRaw for Device<'_> {
     type Raw = u32;
     #[inline]
     unsafe fn as_raw(&self) -> Self::Raw {
@@ -13,64 +44,324 @@ impl AsRaw for Device {
     }
 }
 
-impl Device {
+impl<'acl> Device<'acl> {
     #[inline]
-    pub fn count() -> usize {
+    pub fn count(acl: &Acl) -> usize {
+        Self::try_count(acl).unwrap()
+    }
+
+    #[inline]
+    pub fn try_count(_acl: &Acl) -> Result<usize, AclError> {
         let mut count = 0;
-        acl!(aclrtGetDeviceCount(&mut count));
-        count as _
+        try_acl!(aclrtGetDeviceCount(&mut count))?;
+        Ok(count as _)
     }
 
     #[inline]
-    pub fn new(index: u32) -> Self {
-        Self(index)
+    pub fn new(_acl: &'acl Acl, index: u32) -> Self {
+        Self(index, PhantomData)
     }
 
+    /// 构造设备，序号超出设备数量或无法查询设备数量时返回错误。
+    #[inline]
+    pub fn try_new(acl: &'acl Acl, index: u32) -> Result<Self, NewDevError> {
+        if (index as usize) < Self::try_count(acl)? {
+            Ok(Self::new(acl, index))
+        } else {
+            Err(NoDevError.into())
+        }
+    }
+
+    /// 所有设备。
+    #[inline]
+    pub fn all(acl: &'acl Acl) -> Vec<Self> {
+        Self::iter(acl).collect()
+    }
+
+    /// 遍历所有设备。
+    #[inline]
+    pub fn iter(acl: &'acl Acl) -> Devices<'acl> {
+        Devices(0..Self::count(acl) as _, PhantomData)
+    }
+
+    /// 从物理设备号构造设备。
+    #[inline]
+    pub fn from_physical_id(acl: &'acl Acl, physical_id: u32) -> Self {
+        Self::try_from_physical_id(acl, physical_id).unwrap()
+    }
+
+    /// 从物理设备号构造设备。
+    #[inline]
+    pub fn try_from_physical_id(acl: &'acl Acl, physical_id: u32) -> Result<Self, AclError> {
+        let mut index = 0;
+        try_acl!(aclrtGetLogicDevIdByPhyDevId(physical_id as _, &mut index))?;
+        Ok(Self::new(acl, index as _))
+    }
+
+    /// 从设备序号构造设备，生命周期由调用者保证。
+    #[inline]
+    pub(crate) fn from_index(index: u32) -> Self {
+        Self(index, PhantomData)
+    }
+
+    /// 设备的 SoC 名称。
+    ///
+    /// `aclrtGetSocName` 查询当前设备，因此首次查询在设备的默认上下文上进行，结果按设备缓存。
+    /// 首次查询对当前上下文和设备的影响与 [`Device::mem_info`] 相同。
     #[inline]
     pub fn name(&self) -> &'static CStr {
-        unsafe { CStr::from_ptr(crate::bindings::aclrtGetSocName()) }
+        self.try_name().unwrap()
+    }
+
+    /// 设备的 SoC 名称。
+    ///
+    /// `aclrtGetSocName` 查询当前设备，因此首次查询在设备的默认上下文上进行，结果按设备缓存。
+    /// 首次查询对当前上下文和设备的影响与 [`Device::mem_info`] 相同。
+    pub fn try_name(&self) -> Result<&'static CStr, AclError> {
+        static NAMES: Mutex<Vec<(u32, &'static CStr)>> = Mutex::new(Vec::new());
+
+        if let Some(&(_, name)) = NAMES.lock().unwrap().iter().find(|(i, _)| *i == self.0) {
+            return Ok(name);
+        }
+        let name = self
+            .try_fetch_default()?
+            .apply(|_| unsafe { CStr::from_ptr(crate::bindings::aclrtGetSocName()) });
+        NAMES.lock().unwrap().push((self.0, name));
+        Ok(name)
     }
 
     #[inline]
     pub fn ai_core(&self) -> usize {
-        self.get(ACL_DEVICE_INFO_AI_CORE_NUM) as _
+        self.try_ai_core().unwrap()
+    }
+
+    #[inline]
+    pub fn try_ai_core(&self) -> Result<usize, AclError> {
+        Ok(self.try_get(ACL_DEVICE_INFO_AI_CORE_NUM)? as _)
     }
 
     #[inline]
     pub fn vector_core(&self) -> usize {
-        self.get(ACL_DEVICE_INFO_VECTOR_CORE_NUM) as _
+        self.try_vector_core().unwrap()
+    }
+
+    #[inline]
+    pub fn try_vector_core(&self) -> Result<usize, AclError> {
+        Ok(self.try_get(ACL_DEVICE_INFO_VECTOR_CORE_NUM)? as _)
     }
 
     #[inline]
     pub fn l2_cache(&self) -> MemSize {
-        self.get(ACL_DEVICE_INFO_L2_SIZE).into()
+        self.try_l2_cache().unwrap()
+    }
+
+    #[inline]
+    pub fn try_l2_cache(&self) -> Result<MemSize, AclError> {
+        Ok(self.try_get(ACL_DEVICE_INFO_L2_SIZE)?.into())
+    }
+
+    /// 设备上 `attr` 类型内存的空闲和总量。
+    ///
+    /// 查询在设备的默认上下文上进行。本线程原有当前上下文时，查询后还原；
+    /// 否则运行时无法卸载上下文，本线程可能保持在默认上下文上。
+    /// 没有其他默认上下文存活时，每次查询都会设置并复位设备，
+    /// 频繁查询应在已有的上下文上使用 [`CurrentCtx::mem_info`]。
+    #[inline]
+    pub fn mem_info(&self, attr: aclrtMemAttr) -> MemInfo {
+        self.try_mem_info(attr).unwrap()
+    }
+
+    /// 设备上 `attr` 类型内存的空闲和总量。
+    ///
+    /// 查询在设备的默认上下文上进行。本线程原有当前上下文时，查询后还原；
+    /// 否则运行时无法卸载上下文，本线程可能保持在默认上下文上。
+    /// 没有其他默认上下文存活时，每次查询都会设置并复位设备，
+    /// 频繁查询应在已有的上下文上使用 [`CurrentCtx::try_mem_info`]。
+    #[inline]
+    pub fn try_mem_info(&self, attr: aclrtMemAttr) -> Result<MemInfo, AclError> {
+        self.try_fetch_default()?
+            .apply(|ctx| ctx.try_mem_info(attr))
+    }
+
+    /// 物理设备号，与 npu-smi 显示的设备号一致。
+    #[inline]
+    pub fn physical_id(&self) -> u32 {
+        self.try_physical_id().unwrap()
+    }
+
+    /// 物理设备号，与 npu-smi 显示的设备号一致。
+    #[inline]
+    pub fn try_physical_id(&self) -> Result<u32, AclError> {
+        let mut physical_id = 0;
+        try_acl!(aclrtGetPhyDevIdByLogicDevId(self.0 as _, &mut physical_id))?;
+        Ok(physical_id as _)
     }
 
+    /// 一次性收集设备的所有属性。
     #[inline]
-    fn get(&self, device_info: aclDeviceInfo) -> i64 {
+    pub fn properties(&self) -> DeviceProperties {
+        self.try_properties().unwrap()
+    }
+
+    /// 一次性收集设备的所有属性。
+    pub fn try_properties(&self) -> Result<DeviceProperties, AclError> {
+        // 在同一个默认上下文上查询所有内存信息
+        let (ddr, hbm) = self.try_fetch_default()?.apply(|ctx| {
+            Ok::<_, AclError>((
+                ctx.try_mem_info(ACL_DDR_MEM)?,
+                ctx.try_mem_info(ACL_HBM_MEM)?,
+            ))
+        })?;
+        Ok(DeviceProperties {
+            index: self.0,
+            physical_id: self.try_physical_id()?,
+            name: self.try_name()?.to_string_lossy().into_owned(),
+            ai_core: self.try_ai_core()?,
+            vector_core: self.try_vector_core()?,
+            l2_cache: self.try_l2_cache()?,
+            ddr,
+            hbm,
+        })
+    }
+
+    #[inline]
+    fn try_get(&self, device_info: aclDeviceInfo) -> Result<i64, AclError> {
         let mut ans = 0;
-        acl!(aclGetDeviceCapability(self.0, device_info, &mut ans));
-        ans
+        try_acl!(aclGetDeviceCapability(self.0, device_info, &mut ans))?;
+        Ok(ans)
     }
 
+    /// 格式化设备的名称、核数和内存信息。
+    ///
+    /// 格式化时在设备的默认上下文上查询，对当前上下文和设备的影响与 [`Device::mem_info`] 相同。
     #[inline]
     pub fn info(&self) -> InfoFmt {
         InfoFmt(self)
     }
 }
 
-pub struct InfoFmt<'a>(&'a Device);
+impl fmt::Debug for Device<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Device").field(&self.0).finish()
+    }
+}
+
+/// 解析环境变量 `ASCEND_RT_VISIBLE_DEVICES`，返回进程可见的物理设备号，
+/// 第 i 项对应逻辑设备 i。
+///
+/// 环境变量未设置时返回 `None`。
+pub fn visible_devices() -> Option<Vec<u32>> {
+    std::env::var("ASCEND_RT_VISIBLE_DEVICES")
+        .ok()
+        .map(|value| parse_visible_devices(&value))
+}
+
+/// 逗号分隔的设备号列表，遇到非法或重复的设备号时忽略其后的所有项。
+fn parse_visible_devices(value: &str) -> Vec<u32> {
+    let mut ans = Vec::new();
+    for item in value.split(',') {
+        match item.trim().parse() {
+            Ok(id) if !ans.contains(&id) => ans.push(id),
+            _ => break,
+        }
+    }
+    ans
+}
+
+/// 设备迭代器，见 [`Device::iter`]。
+#[derive(Clone, Debug)]
+pub struct Devices<'acl>(Range<u32>, PhantomData<&'acl Acl>);
+
+impl<'acl> Iterator for Devices<'acl> {
+    type Item = Device<'acl>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(Device::from_index)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Devices<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(Device::from_index)
+    }
+}
+
+impl ExactSizeIterator for Devices<'_> {}
+
+impl CurrentCtx {
+    /// 当前设备上 `attr` 类型内存的空闲和总量。
+    #[inline]
+    pub fn mem_info(&self, attr: aclrtMemAttr) -> MemInfo {
+        self.try_mem_info(attr).unwrap()
+    }
+
+    /// 当前设备上 `attr` 类型内存的空闲和总量。
+    #[inline]
+    pub fn try_mem_info(&self, attr: aclrtMemAttr) -> Result<MemInfo, AclError> {
+        let mut free = 0;
+        let mut total = 0;
+        try_acl!(aclrtGetMemInfo(attr, &mut free, &mut total))?;
+        Ok(MemInfo {
+            free: MemSize(free),
+            total: MemSize(total),
+        })
+    }
+}
+
+/// 内存的空闲和总量。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemInfo {
+    pub free: MemSize,
+    pub total: MemSize,
+}
+
+/// 设备属性的快照。
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceProperties {
+    /// 逻辑设备号。
+    pub index: u32,
+    /// 物理设备号。
+    pub physical_id: u32,
+    /// SoC 名称。
+    pub name: String,
+    pub ai_core: usize,
+    pub vector_core: usize,
+    pub l2_cache: MemSize,
+    pub ddr: MemInfo,
+    pub hbm: MemInfo,
+}
+
+pub struct InfoFmt<'a>(&'a Device<'a>);
 
 impl fmt::Display for InfoFmt<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Dev{}: {}", self.0 .0, self.0.name().to_str().unwrap())?;
         writeln!(f, "  AI Core: {}", self.0.ai_core())?;
         writeln!(f, "  Vector Core: {}", self.0.vector_core())?;
-        writeln!(f, "  L2 Cache: {}", self.0.l2_cache())
+        writeln!(f, "  L2 Cache: {}", self.0.l2_cache())?;
+        self.0.fetch_default().apply(|ctx| {
+            for (name, attr) in [("DDR", ACL_DDR_MEM), ("HBM", ACL_HBM_MEM)] {
+                let MemInfo { free, total } = ctx.mem_info(attr);
+                if total.0 != 0 {
+                    writeln!(f, "  {name}: {free} free / {total} total")?
+                }
+            }
+            Ok(())
+        })
     }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(transparent)]
 pub struct MemSize(pub usize);
 
@@ -104,9 +395,34 @@ impl From<i64> for MemSize {
 
 #[test]
 fn test() {
-    crate::init();
-    for i in 0..Device::count() {
-        println!("{}", Device::new(i as _).info());
+    let acl = Acl::init();
+    if let Some(visible) = visible_devices() {
+        println!("visible devices: {visible:?}");
+    }
+    for dev in Device::iter(&acl) {
+        assert_eq!(Device::from_physical_id(&acl, dev.physical_id()), dev);
+        println!("{}", dev.info());
+        println!("{:#?}", dev.properties());
+        // 本线程原有当前上下文时，查询后还原
+        dev.context().apply(|ctx| {
+            dev.mem_info(ACL_HBM_MEM);
+            assert_eq!(
+                CurrentCtx::apply_current(|current| unsafe { current.as_raw() }),
+                Ok(unsafe { ctx.as_raw() })
+            )
+        });
     }
-    crate::finalize()
+    assert_eq!(
+        Device::try_new(&acl, Device::count(&acl) as _),
+        Err(NewDevError::NoDev(NoDevError))
+    );
+}
+
+#[test]
+fn test_visible_devices() {
+    assert_eq!(parse_visible_devices("0,1,2"), [0, 1, 2]);
+    assert_eq!(parse_visible_devices(" 4, 2 "), [4, 2]);
+    assert_eq!(parse_visible_devices("3,x,1"), [3]);
+    assert_eq!(parse_visible_devices("1,1,2"), [1]);
+    assert_eq!(parse_visible_devices(""), [] as [u32; 0]);
 }
@@ -0,0 +1,235 @@
+﻿use crate::bindings::{aclError, aclGetRecentErrMsg};
+use std::{error::Error, ffi::CStr, fmt, panic::Location};
+
+/// AscendCL 接口调用失败时返回的错误。
+///
+/// 记录错误码、失败的调用、调用位置以及运行时提供的详细错误信息。
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AclError {
+    code: aclError,
+    call: &'static str,
+    location: &'static Location<'static>,
+    message: Option<String>,
+}
+
+impl AclError {
+    /// 检查 AscendCL 接口的返回值，非 0 时构造错误。
+    ///
+    /// 运行时的详细错误信息是线程局部的，因此必须在失败的调用之后立即检查。
+    /// 通常由 [`try_acl!`](crate::try_acl) 宏调用。
+    #[inline]
+    pub fn check(
+        code: aclError,
+        call: &'static str,
+        location: &'static Location<'static>,
+    ) -> Result<(), Self> {
+        if code == 0 {
+            Ok(())
+        } else {
+            Err(Self {
+                code,
+                call,
+                location,
+                message: recent_err_msg(),
+            })
+        }
+    }
+
+    /// 错误码。
+    #[inline]
+    pub const fn code(&self) -> aclError {
+        self.code
+    }
+
+    /// 失败的调用表达式。
+    #[inline]
+    pub const fn call(&self) -> &'static str {
+        self.call
+    }
+
+    /// 失败的接口名。
+    #[inline]
+    pub fn function(&self) -> &'static str {
+        let call = self.call;
+        call[..call.find('(').unwrap_or(call.len())].trim()
+    }
+
+    /// 错误码对应的已知错误，未知的错误码返回 `None`。
+    #[inline]
+    pub const fn kind(&self) -> Option<ErrorCode> {
+        ErrorCode::from_raw(self.code)
+    }
+
+    /// 失败调用的源码位置。
+    #[inline]
+    pub const fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// 失败时通过 `aclGetRecentErrMsg` 取得的详细错误信息。
+    #[inline]
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+fn recent_err_msg() -> Option<String> {
+    let ptr = unsafe { aclGetRecentErrMsg() };
+    if ptr.is_null() {
+        return None;
+    }
+    let msg = unsafe { CStr::from_ptr(ptr) }.to_string_lossy();
+    let msg = msg.trim();
+    if msg.is_empty() {
+        None
+    } else {
+        Some(msg.into())
+    }
+}
+
+impl fmt::Display for AclError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed with ", self.function())?;
+        match self.kind() {
+            Some(kind) => write!(f, "{kind} ({})", self.code)?,
+            None => write!(f, "error code {}", self.code)?,
+        }
+        write!(f, " at {}", self.location)?;
+        write!(f, "\n  call: {}", self.call)?;
+        if let Some(msg) = &self.message {
+            write!(f, "\n  message: {msg}")?
+        }
+        Ok(())
+    }
+}
+
+impl Error for AclError {}
+
+include!(concat!(env!("OUT_DIR"), "/error_codes.rs"));
+
+/// 错误码的分类。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ErrorCategory {
+    /// 参数校验错误，错误码形如 1XXXXX。
+    Parameter,
+    /// 资源申请错误，错误码形如 2XXXXX 或 3XXXXX。
+    Resource,
+    /// 功能不支持。
+    FeatureNotSupported,
+    /// 内部错误，错误码形如 5XXXXX。
+    Internal,
+}
+
+impl ErrorCode {
+    /// 错误码的分类。
+    pub fn category(self) -> ErrorCategory {
+        let name = self.name();
+        if name.contains("NOT_SUPPORT") || name.contains("UNSUPPORTED") {
+            return ErrorCategory::FeatureNotSupported;
+        }
+        match self as aclError / 100000 {
+            1 => ErrorCategory::Parameter,
+            2 | 3 => ErrorCategory::Resource,
+            _ => ErrorCategory::Internal,
+        }
+    }
+
+    /// 内存或运行时资源耗尽的错误可能在释放资源后重试成功。
+    ///
+    /// 设备不存在、句柄无效、权限不足等资源错误重试也不会成功，不在此列。
+    pub fn is_retryable(self) -> bool {
+        const EXHAUSTED: [&str; 5] = [
+            "ACL_ERROR_BAD_ALLOC",
+            "ACL_ERROR_STORAGE_OVER_LIMIT",
+            "ACL_ERROR_RT_MEMORY_ALLOCATION",
+            "ACL_ERROR_RT_RESOURCE_ALLOC_FAIL",
+            "ACL_ERROR_RT_DEVIDE_OOM",
+        ];
+        let name = self.name();
+        // ACL_ERROR_RT_NO_EVENT_RESOURCE、ACL_ERROR_RT_NO_STREAM_RESOURCE 等
+        EXHAUSTED.contains(&name)
+            || (name.starts_with("ACL_ERROR_RT_NO_") && name.ends_with("_RESOURCE"))
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[test]
+fn test_check() {
+    let location = Location::caller();
+    assert_eq!(AclError::check(0, "aclInit(null())", location), Ok(()));
+
+    let err = AclError::check(207001, "aclrtMalloc(&mut ptr, len, policy)", location).unwrap_err();
+    assert_eq!(err.code(), 207001);
+    assert_eq!(err.function(), "aclrtMalloc");
+    assert_eq!(err.call(), "aclrtMalloc(&mut ptr, len, policy)");
+    assert_eq!(err.kind(), Some(ErrorCode::ACL_ERROR_RT_MEMORY_ALLOCATION));
+    assert!(err
+        .to_string()
+        .starts_with("aclrtMalloc failed with ACL_ERROR_RT_MEMORY_ALLOCATION (207001) at "));
+
+    let err = AclError::check(-1, "aclrtFree(ptr)", location).unwrap_err();
+    assert_eq!(err.kind(), None);
+    assert!(err
+        .to_string()
+        .starts_with("aclrtFree failed with error code -1 at "));
+}
+
+#[test]
+fn test_category() {
+    use ErrorCode::*;
+
+    assert_eq!(
+        ACL_ERROR_RT_PARAM_INVALID.category(),
+        ErrorCategory::Parameter
+    );
+    assert_eq!(
+        ACL_ERROR_RT_MEMORY_ALLOCATION.category(),
+        ErrorCategory::Resource
+    );
+    assert_eq!(
+        ACL_ERROR_RT_FEATURE_NOT_SUPPORT.category(),
+        ErrorCategory::FeatureNotSupported
+    );
+    assert_eq!(
+        ACL_ERROR_RT_INTERNAL_ERROR.category(),
+        ErrorCategory::Internal
+    );
+    assert_eq!(
+        ACL_ERROR_FEATURE_UNSUPPORTED.category(),
+        ErrorCategory::FeatureNotSupported
+    );
+    assert_eq!(
+        ACL_ERROR_PROF_MODULES_UNSUPPORTED.category(),
+        ErrorCategory::FeatureNotSupported
+    );
+
+    for code in [
+        ACL_ERROR_BAD_ALLOC,
+        ACL_ERROR_STORAGE_OVER_LIMIT,
+        ACL_ERROR_RT_MEMORY_ALLOCATION,
+        ACL_ERROR_RT_RESOURCE_ALLOC_FAIL,
+        ACL_ERROR_RT_DEVIDE_OOM,
+        ACL_ERROR_RT_NO_EVENT_RESOURCE,
+        ACL_ERROR_RT_NO_STREAM_RESOURCE,
+    ] {
+        assert!(code.is_retryable(), "{code}")
+    }
+    for code in [
+        ACL_ERROR_RT_PARAM_INVALID,
+        ACL_ERROR_INVALID_DEVICE,
+        ACL_ERROR_INVALID_RESOURCE_HANDLE,
+        ACL_ERROR_FEATURE_UNSUPPORTED,
+        ACL_ERROR_PROF_MODULES_UNSUPPORTED,
+        ACL_ERROR_RT_NO_DEVICE,
+        ACL_ERROR_RT_NO_PERMISSION,
+        ACL_ERROR_RT_MEMORY_FREE,
+    ] {
+        assert!(!code.is_retryable(), "{code}")
+    }
+    assert_eq!(ErrorCode::from_raw(107002), Some(ACL_ERROR_RT_CONTEXT_NULL));
+}
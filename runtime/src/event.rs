@@ -1,21 +1,77 @@
-﻿use crate::{bindings::aclrtEvent, CurrentCtx, Stream};
+﻿use crate::{
+    bindings::{aclrtEvent, aclrtEventRecordedStatus::*, ACL_EVENT_SYNC, ACL_EVENT_TIME_LINE},
+    leak::{report_release, ResourceKind},
+    AclError, BenchReport, CurrentCtx, Stream,
+};
 use context_spore::{impl_spore, AsRaw};
-use std::{marker::PhantomData, ptr::null_mut, time::Duration};
+use std::{marker::PhantomData, ops::BitOr, ptr::null_mut, time::Duration};
 
 impl_spore!(Event and EventSpore by (CurrentCtx, aclrtEvent));
 
+/// 创建事件的选项。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct EventFlags(u32);
+
+impl EventFlags {
+    /// 用于流间同步。
+    pub const SYNC: Self = Self(ACL_EVENT_SYNC);
+    /// 记录时间戳，用于计时。
+    pub const TIME_LINE: Self = Self(ACL_EVENT_TIME_LINE);
+}
+
+impl BitOr for EventFlags {
+    type Output = Self;
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl CurrentCtx {
+    /// 创建一个尚未记录的事件，可以反复记录。
+    #[inline]
+    pub fn event(&self, flags: EventFlags) -> Event<'_> {
+        self.try_event(flags).unwrap()
+    }
+
+    /// 创建一个尚未记录的事件，可以反复记录。
+    #[inline]
+    pub fn try_event(&self, flags: EventFlags) -> Result<Event<'_>, AclError> {
+        let mut event = null_mut();
+        try_acl!(aclrtCreateEventWithFlag(&mut event, flags.0))?;
+        Ok(Event(unsafe { self.wrap_raw(event) }, PhantomData))
+    }
+}
+
 impl<'ctx> Stream<'ctx> {
     pub fn record(&self) -> Event<'ctx> {
+        self.try_record().unwrap()
+    }
+
+    pub fn try_record(&self) -> Result<Event<'ctx>, AclError> {
         let mut event = null_mut();
-        acl!(aclrtCreateEvent(&mut event));
-        acl!(aclrtRecordEvent(event, self.as_raw()));
-        Event(unsafe { self.ctx().wrap_raw(event) }, PhantomData)
+        try_acl!(aclrtCreateEvent(&mut event))?;
+        let mut event = Event(unsafe { self.ctx().wrap_raw(event) }, PhantomData);
+        self.try_record_into(&mut event)?;
+        Ok(event)
+    }
+
+    /// 在流上重新记录已有的事件。
+    #[inline]
+    pub fn record_into(&self, event: &mut Event) {
+        self.try_record_into(event).unwrap()
+    }
+
+    /// 在流上重新记录已有的事件。
+    #[inline]
+    pub fn try_record_into(&self, event: &mut Event) -> Result<(), AclError> {
+        try_acl!(aclrtRecordEvent(event.0.rss, self.as_raw()))
     }
 }
 
 impl Drop for Event<'_> {
     fn drop(&mut self) {
-        acl!(aclrtDestroyEvent(self.0.rss))
+        report_release(ResourceKind::Event, try_acl!(aclrtDestroyEvent(self.0.rss)))
     }
 }
 
@@ -30,7 +86,12 @@ impl AsRaw for Event<'_> {
 impl Stream<'_> {
     #[inline]
     pub fn wait_for(&self, event: &Event) {
-        acl!(aclrtStreamWaitEvent(self.as_raw(), event.0.rss))
+        self.try_wait_for(event).unwrap()
+    }
+
+    #[inline]
+    pub fn try_wait_for(&self, event: &Event) -> Result<(), AclError> {
+        try_acl!(aclrtStreamWaitEvent(self.as_raw(), event.0.rss))
     }
 
     pub fn bench(&self, mut f: impl FnMut(usize, &Self), times: usize, warm_up: usize) -> Duration {
@@ -45,18 +106,147 @@ impl Stream<'_> {
         end.synchronize();
         end.elapse_from(&start).div_f32(times as _)
     }
+
+    /// 为每次执行单独计时，统计执行时间的分布。
+    ///
+    /// 每次执行后记录一个事件，相邻两个事件的间隔作为一次执行的时间，因此包含提交任务的间隙。
+    /// 只使用固定数量的事件，每执行 32 次同步一次并读取计时。
+    ///
+    /// # Panics
+    ///
+    /// `times` 为 0 时 panic。
+    #[inline]
+    pub fn bench_stats(
+        &self,
+        f: impl FnMut(usize, &Self),
+        times: usize,
+        warm_up: usize,
+    ) -> BenchReport {
+        self.try_bench_stats(f, times, warm_up).unwrap()
+    }
+
+    /// 为每次执行单独计时，统计执行时间的分布。
+    ///
+    /// 每次执行后记录一个事件，相邻两个事件的间隔作为一次执行的时间，因此包含提交任务的间隙。
+    /// 只使用固定数量的事件，每执行 32 次同步一次并读取计时。
+    ///
+    /// # Panics
+    ///
+    /// `times` 为 0 时 panic，运行时的错误通过返回值报告。
+    pub fn try_bench_stats(
+        &self,
+        mut f: impl FnMut(usize, &Self),
+        times: usize,
+        warm_up: usize,
+    ) -> Result<BenchReport, AclError> {
+        /// 每批执行的次数。
+        const BATCH: usize = 32;
+
+        assert!(times > 0, "bench at least once");
+        for i in 0..warm_up {
+            f(i, self);
+        }
+        // 批内相邻两次执行共用一个事件
+        let ctx = self.ctx();
+        let mut events = (0..=BATCH.min(times))
+            .map(|_| ctx.try_event(EventFlags::TIME_LINE))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut samples = Vec::with_capacity(times);
+        for batch in (0..times).step_by(BATCH) {
+            let n = BATCH.min(times - batch);
+            // 每批重新记录起点，不计入批间同步的时间
+            self.try_record_into(&mut events[0])?;
+            for (i, event) in events[1..=n].iter_mut().enumerate() {
+                f(batch + i, self);
+                self.try_record_into(event)?;
+            }
+            events[n].try_synchronize()?;
+            for pair in events[..=n].windows(2) {
+                samples.push(
+                    pair[1]
+                        .try_elapse_from(&pair[0])?
+                        .expect("batch is synchronized"),
+                )
+            }
+        }
+        Ok(BenchReport::from_samples(samples).expect("times > 0"))
+    }
 }
 
 impl Event<'_> {
     #[inline]
     pub fn synchronize(&self) {
-        acl!(aclrtSynchronizeEvent(self.0.rss))
+        self.try_synchronize().unwrap()
     }
 
+    #[inline]
+    pub fn try_synchronize(&self) -> Result<(), AclError> {
+        try_acl!(aclrtSynchronizeEvent(self.0.rss))
+    }
+
+    /// 在流上复位事件，复位后可以重新记录。
+    #[inline]
+    pub fn reset(&mut self, stream: &Stream) {
+        self.try_reset(stream).unwrap()
+    }
+
+    /// 在流上复位事件，复位后可以重新记录。
+    #[inline]
+    pub fn try_reset(&mut self, stream: &Stream) -> Result<(), AclError> {
+        try_acl!(aclrtResetEvent(self.0.rss, stream.as_raw()))
+    }
+
+    /// 事件之前的任务是否已经完成，不阻塞。
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.try_is_complete().unwrap()
+    }
+
+    /// 事件之前的任务是否已经完成，不阻塞。
+    #[inline]
+    pub fn try_is_complete(&self) -> Result<bool, AclError> {
+        let mut status = ACL_EVENT_RECORDED_STATUS_NOT_READY;
+        try_acl!(aclrtQueryEventStatus(self.0.rss, &mut status))?;
+        Ok(status == ACL_EVENT_RECORDED_STATUS_COMPLETE)
+    }
+
+    /// 从 `start` 到这个事件经过的时间。
+    ///
+    /// 两个事件都必须已经完成。
     #[inline]
     pub fn elapse_from(&self, start: &Self) -> Duration {
+        self.try_elapse_from(start)
+            .unwrap()
+            .expect("events are not complete")
+    }
+
+    /// 从 `start` 到这个事件经过的时间，任一事件未完成时返回 `None`，不阻塞。
+    pub fn try_elapse_from(&self, start: &Self) -> Result<Option<Duration>, AclError> {
+        if !start.try_is_complete()? || !self.try_is_complete()? {
+            return Ok(None);
+        }
         let mut ms = 0.0;
-        acl!(aclrtEventElapsedTime(&mut ms, start.0.rss, self.0.rss));
-        Duration::from_secs_f32(ms * 1e-3)
+        try_acl!(aclrtEventElapsedTime(&mut ms, start.0.rss, self.0.rss))?;
+        Ok(Some(Duration::from_secs_f32(ms * 1e-3)))
+    }
+}
+
+#[test]
+fn test_bench_stats() {
+    use crate::Device;
+
+    let acl = crate::Acl::init();
+    if Device::count(&acl) == 0 {
+        return;
     }
+    Device::new(&acl, 0).context().apply(|ctx| {
+        let stream = ctx.stream();
+        let data = vec![0u8; 1 << 20];
+        let mut dev = ctx.malloc::<u8>(data.len());
+        let report = stream.bench_stats(|_, s| s.memcpy_h2d(&mut dev, &data), 100, 10);
+        assert_eq!(report.samples().len(), 100);
+        assert!(report.min() <= report.median() && report.median() <= report.p99());
+        println!("{report}");
+        println!("{:.2} GB/s", report.bytes_per_sec(data.len()) / 1e9)
+    });
 }
@@ -0,0 +1,132 @@
+﻿use crate::{AclError, Event, Stream};
+use std::{
+    future::{poll_fn, Future},
+    mem::take,
+    sync::{Arc, Condvar, Mutex, Once},
+    task::{Poll, Waker},
+    thread,
+    time::Duration,
+};
+
+impl Stream<'_> {
+    /// 流上已提交的任务全部完成时就绪的 [`Future`]，不依赖特定的异步运行时。
+    ///
+    /// 通过主机函数通知完成，不阻塞任何线程。
+    #[inline]
+    pub fn completed(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.try_completed().unwrap()
+    }
+
+    /// 流上已提交的任务全部完成时就绪的 [`Future`]，不依赖特定的异步运行时。
+    ///
+    /// 通过主机函数通知完成，不阻塞任何线程。
+    pub fn try_completed(&self) -> Result<impl Future<Output = ()> + Send + 'static, AclError> {
+        let state = Arc::new(Mutex::new((false, None::<Waker>)));
+        let state_ = state.clone();
+        self.try_launch_host_fn(move || {
+            let mut state = state_.lock().unwrap();
+            state.0 = true;
+            if let Some(waker) = state.1.take() {
+                waker.wake()
+            }
+        })?;
+        Ok(poll_fn(move |cx| {
+            let mut state = state.lock().unwrap();
+            if state.0 {
+                Poll::Ready(())
+            } else {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }))
+    }
+}
+
+impl Event<'_> {
+    /// 事件完成时就绪的 [`Future`]，不依赖特定的异步运行时。
+    ///
+    /// 事件未完成时由共享的轮询线程定期唤醒，再次查询事件状态。
+    pub fn wait_async(&self) -> impl Future<Output = Result<(), AclError>> + '_ {
+        poll_fn(|cx| match self.try_is_complete() {
+            Ok(true) => Poll::Ready(Ok(())),
+            Ok(false) => {
+                wake_later(cx.waker().clone());
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(e)),
+        })
+    }
+}
+
+/// 轮询线程唤醒等待者的间隔。
+const POLL_INTERVAL: Duration = Duration::from_micros(200);
+
+static WAKERS: Mutex<Vec<Waker>> = Mutex::new(Vec::new());
+static NOT_EMPTY: Condvar = Condvar::new();
+static POLLER: Once = Once::new();
+
+/// 在下一个轮询间隔后唤醒。
+fn wake_later(waker: Waker) {
+    POLLER.call_once(|| {
+        thread::Builder::new()
+            .name("ascendcl-poller".into())
+            .spawn(poll)
+            .unwrap();
+    });
+    WAKERS.lock().unwrap().push(waker);
+    NOT_EMPTY.notify_one()
+}
+
+fn poll() {
+    loop {
+        let mut wakers = WAKERS.lock().unwrap();
+        while wakers.is_empty() {
+            wakers = NOT_EMPTY.wait(wakers).unwrap()
+        }
+        drop(wakers);
+
+        thread::sleep(POLL_INTERVAL);
+        for waker in take(&mut *WAKERS.lock().unwrap()) {
+            waker.wake()
+        }
+    }
+}
+
+#[test]
+fn test() {
+    use crate::Device;
+    use std::{
+        pin::pin,
+        task::{Context, Wake},
+        thread::Thread,
+    };
+
+    struct Unpark(Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark()
+        }
+    }
+    fn block_on<T>(f: impl Future<Output = T>) -> T {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut f = pin!(f);
+        loop {
+            match f.as_mut().poll(&mut cx) {
+                Poll::Ready(ans) => break ans,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    let acl = crate::Acl::init();
+    if Device::count(&acl) == 0 {
+        return;
+    }
+    Device::new(&acl, 0).context().apply(|ctx| {
+        let stream = ctx.stream();
+        let event = stream.record();
+        block_on(stream.completed());
+        block_on(event.wait_async()).unwrap();
+    });
+}
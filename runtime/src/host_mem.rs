@@ -1,4 +1,7 @@
-﻿use crate::{Blob, CurrentCtx};
+﻿use crate::{
+    leak::{report_release, ResourceKind},
+    AclError, Blob, CurrentCtx,
+};
 use context_spore::{impl_spore, AsRaw};
 use std::{
     alloc::Layout,
@@ -13,16 +16,26 @@ impl_spore!(HostMem and HostMemSpore by (CurrentCtx, Blob<*mut c_void>));
 
 impl CurrentCtx {
     pub fn malloc_host<T: Copy>(&self, len: usize) -> HostMem {
+        self.try_malloc_host::<T>(len).unwrap()
+    }
+
+    pub fn try_malloc_host<T: Copy>(&self, len: usize) -> Result<HostMem<'_>, AclError> {
         let len = Layout::array::<T>(len).unwrap().size();
         let mut ptr = null_mut();
-        acl!(aclrtMallocHost(&mut ptr, len));
-        HostMem(unsafe { self.wrap_raw(Blob { ptr, len }) }, PhantomData)
+        try_acl!(aclrtMallocHost(&mut ptr, len))?;
+        Ok(HostMem(
+            unsafe { self.wrap_raw(Blob { ptr, len }) },
+            PhantomData,
+        ))
     }
 }
 
 impl Drop for HostMem<'_> {
     fn drop(&mut self) {
-        acl!(aclrtFreeHost(self.0.rss.ptr))
+        report_release(
+            ResourceKind::HostMem,
+            try_acl!(aclrtFreeHost(self.0.rss.ptr)),
+        )
     }
 }
 
@@ -68,13 +81,13 @@ impl DerefMut for HostMemSpore {
 
 #[test]
 fn test_behavior() {
-    crate::init();
-    if crate::Device::count() == 0 {
+    let acl = crate::Acl::init();
+    if crate::Device::count(&acl) == 0 {
         return;
     }
 
     let mut ptr = null_mut();
-    crate::Device::new(0).context().apply(|_| {
+    crate::Device::new(&acl, 0).context().apply(|_| {
         acl!(aclrtMallocHost(&mut ptr, 128));
         acl!(aclrtFreeHost(ptr))
     });
@@ -87,12 +100,12 @@ fn bench() {
     use rand::Rng;
     use std::time::{Duration, Instant};
 
-    crate::init();
-    if crate::Device::count() == 0 {
+    let acl = crate::Acl::init();
+    if crate::Device::count(&acl) == 0 {
         return;
     }
 
-    crate::Device::new(0).context().apply(|ctx| {
+    crate::Device::new(&acl, 0).context().apply(|ctx| {
         let mut pagable = vec![0.0f32; 256 << 10];
         rand::thread_rng().fill(&mut *pagable);
         let pagable = unsafe {
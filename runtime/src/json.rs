@@ -0,0 +1,39 @@
+﻿use std::fmt::Write;
+
+/// json 字符串字面量。
+pub(crate) fn string(s: &str) -> String {
+    let mut ans = String::with_capacity(s.len() + 2);
+    ans.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => ans.push_str("\\\""),
+            '\\' => ans.push_str("\\\\"),
+            '\n' => ans.push_str("\\n"),
+            '\r' => ans.push_str("\\r"),
+            '\t' => ans.push_str("\\t"),
+            c if c.is_control() => write!(ans, "\\u{:04x}", c as u32).unwrap(),
+            c => ans.push(c),
+        }
+    }
+    ans.push('"');
+    ans
+}
+
+/// 由已经生成的元素组成的 json 数组。
+pub(crate) fn array(items: impl IntoIterator<Item = String>) -> String {
+    format!("[{}]", items.into_iter().collect::<Vec<_>>().join(","))
+}
+
+/// 按顺序由键和已经生成的值组成的 json 对象。
+pub(crate) fn object(fields: Vec<(&str, String)>) -> String {
+    let fields = fields
+        .into_iter()
+        .map(|(key, value)| format!("{}:{value}", string(key)))
+        .collect::<Vec<_>>();
+    format!("{{{}}}", fields.join(","))
+}
+
+#[test]
+fn test_escape() {
+    assert_eq!(string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+}
@@ -0,0 +1,44 @@
+﻿use crate::AclError;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+/// 由析构函数释放的资源类型。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ResourceKind {
+    Context,
+    DevMem,
+    HostMem,
+    Stream,
+    Event,
+}
+
+static LEAKED: [AtomicUsize; 5] = [const { AtomicUsize::new(0) }; 5];
+
+/// 因释放失败而泄漏的资源数量。
+#[inline]
+pub fn leaked(kind: ResourceKind) -> usize {
+    LEAKED[kind as usize].load(Relaxed)
+}
+
+/// 报告析构函数中释放资源的结果。
+///
+/// 析构函数可能在栈展开时调用，此时 panic 将导致进程终止，因此只记录日志和泄漏计数。
+pub(crate) fn report_release(kind: ResourceKind, result: Result<(), AclError>) {
+    if let Err(e) = result {
+        LEAKED[kind as usize].fetch_add(1, Relaxed);
+        log::error!("failed to release {kind:?}: {e}");
+    }
+}
+
+#[test]
+fn test_report() {
+    use std::panic::Location;
+
+    let before = leaked(ResourceKind::Event);
+    report_release(ResourceKind::Event, Ok(()));
+    assert_eq!(leaked(ResourceKind::Event), before);
+    report_release(
+        ResourceKind::Event,
+        AclError::check(-1, "aclrtDestroyEvent(event)", Location::caller()),
+    );
+    assert_eq!(leaked(ResourceKind::Event), before + 1);
+}
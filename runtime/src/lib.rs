@@ -8,47 +8,71 @@ pub mod bindings {
 
     #[macro_export]
     macro_rules! acl {
+        ($f:expr) => {{
+            if let Err(e) = $crate::try_acl!($f) {
+                panic!("{e}")
+            }
+        }};
+    }
+
+    #[macro_export]
+    macro_rules! try_acl {
         ($f:expr) => {{
             #[allow(unused_imports)]
             use $crate::bindings::*;
             #[allow(unused_unsafe, clippy::macro_metavars_in_unsafe)]
             let err = unsafe { $f };
-            assert_eq!(err, 0);
+            $crate::AclError::check(err, stringify!($f), ::std::panic::Location::caller())
         }};
     }
 }
 
-#[inline(always)]
-pub fn init() {
-    acl!(aclInit(std::ptr::null()));
-}
-
-#[inline(always)]
-pub fn finalize() {
-    acl!(aclFinalize());
-}
-
-#[inline]
-pub fn version() -> (i32, i32, i32) {
-    let mut ans = (0, 0, 0);
-    acl!(aclrtGetVersion(&mut ans.0, &mut ans.1, &mut ans.2));
-    ans
-}
-
+mod acl;
+mod bench;
+mod callback;
+mod config;
 mod context;
 mod dev_mem;
 mod device;
+mod error;
 mod event;
+mod future;
 mod host_mem;
+mod json;
+mod leak;
+mod options;
+mod peer;
+mod soc;
 mod stream;
+mod trace;
+mod version;
 
-pub use context::{Context, CurrentCtx, NoCtxError};
+pub use acl::{Acl, InitError};
+pub use bench::BenchReport;
+pub use config::{
+    AclConfig, AicMetrics, DumpConfig, DumpData, DumpMode, ErrMsgMode, ProfilerConfig,
+};
+pub use context::{Context, ContextGuard, CurrentCtx, NoCtxError};
 pub use context_spore::{impl_spore, AsRaw, ContextResource, ContextSpore, RawContainer};
-pub use dev_mem::{memcpy_d2d, memcpy_d2h, memcpy_h2d, DevByte, DevMem, DevMemSpore};
-pub use device::Device;
-pub use event::{Event, EventSpore};
+pub use dev_mem::{
+    memcpy_d2d, memcpy_d2h, memcpy_h2d, memcpy_peer, try_memcpy_d2d, try_memcpy_d2h,
+    try_memcpy_h2d, try_memcpy_peer, DevByte, DevMem, DevMemSpore,
+};
+pub use device::{
+    visible_devices, Device, DeviceProperties, Devices, MemInfo, MemSize, NewDevError, NoDevError,
+};
+pub use error::{AclError, ErrorCategory, ErrorCode};
+pub use event::{Event, EventFlags, EventSpore};
 pub use host_mem::{HostMem, HostMemSpore};
-pub use stream::{Stream, StreamSpore};
+pub use leak::{leaked, ResourceKind};
+pub use options::{SatMode, SysParam};
+pub use peer::Topology;
+pub use soc::SocVersion;
+pub use stream::{Stream, StreamBuilder, StreamSpore};
+pub use trace::{Recorder, Span, Timeline};
+pub use version::{
+    try_version, version, ParseVersionError, Release, RuntimeVersion, ToolkitVersion,
+};
 
 struct Blob<P> {
     ptr: P,
@@ -57,7 +81,6 @@ struct Blob<P> {
 
 #[test]
 fn test_bindings() {
-    init();
+    let _acl = Acl::init();
     println!("version: {:?}", version());
-    finalize()
 }
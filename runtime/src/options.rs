@@ -0,0 +1,314 @@
+﻿use crate::{
+    bindings::{aclSysParamOpt, aclrtFloatOverflowMode},
+    AclError, Context, CurrentCtx,
+};
+use std::{
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+    time::Duration,
+};
+
+/// 上下文的系统参数。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SysParam {
+    /// 确定性计算，开启后相同输入多次执行的结果一致。
+    Deterministic,
+    /// 算子执行时检查越界等错误。
+    DebugKernel,
+}
+
+impl SysParam {
+    #[inline]
+    fn as_raw(self) -> aclSysParamOpt {
+        match self {
+            Self::Deterministic => aclSysParamOpt::ACL_OPT_DETERMINISTIC,
+            Self::DebugKernel => aclSysParamOpt::ACL_OPT_ENABLE_DEBUG_KERNEL,
+        }
+    }
+}
+
+/// 浮点计算溢出时的处理模式。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SatMode {
+    /// 溢出时饱和为最大值或最小值。
+    Saturation,
+    /// 溢出时输出 Inf 或 NaN。
+    InfNan,
+}
+
+impl CurrentCtx {
+    /// 设置当前上下文的系统参数。
+    #[inline]
+    pub fn set_sys_param(&self, param: SysParam, value: i64) {
+        self.try_set_sys_param(param, value).unwrap()
+    }
+
+    /// 设置当前上下文的系统参数。
+    #[inline]
+    pub fn try_set_sys_param(&self, param: SysParam, value: i64) -> Result<(), AclError> {
+        try_acl!(aclrtCtxSetSysParamOpt(param.as_raw(), value))
+    }
+
+    /// 查询当前上下文的系统参数。
+    #[inline]
+    pub fn sys_param(&self, param: SysParam) -> i64 {
+        self.try_sys_param(param).unwrap()
+    }
+
+    /// 查询当前上下文的系统参数。
+    #[inline]
+    pub fn try_sys_param(&self, param: SysParam) -> Result<i64, AclError> {
+        let mut value = 0;
+        try_acl!(aclrtCtxGetSysParamOpt(param.as_raw(), &mut value))?;
+        Ok(value)
+    }
+
+    /// 开启或关闭当前上下文的确定性计算。
+    #[inline]
+    pub fn set_deterministic(&self, enable: bool) {
+        self.set_sys_param(SysParam::Deterministic, enable as _)
+    }
+
+    /// 当前上下文是否开启确定性计算。
+    #[inline]
+    pub fn is_deterministic(&self) -> bool {
+        self.sys_param(SysParam::Deterministic) != 0
+    }
+
+    /// 设置当前设备的浮点溢出模式。
+    #[inline]
+    pub fn set_sat_mode(&self, mode: SatMode) {
+        self.try_set_sat_mode(mode).unwrap()
+    }
+
+    /// 设置当前设备的浮点溢出模式。
+    #[inline]
+    pub fn try_set_sat_mode(&self, mode: SatMode) -> Result<(), AclError> {
+        let mode = match mode {
+            SatMode::Saturation => aclrtFloatOverflowMode::ACL_RT_OVERFLOW_MODE_SATURATION,
+            SatMode::InfNan => aclrtFloatOverflowMode::ACL_RT_OVERFLOW_MODE_INFNAN,
+        };
+        try_acl!(aclrtSetDeviceSatMode(mode))
+    }
+
+    /// 查询当前设备的浮点溢出模式，未定义的模式返回 `None`。
+    #[inline]
+    pub fn sat_mode(&self) -> Option<SatMode> {
+        self.try_sat_mode().unwrap()
+    }
+
+    /// 查询当前设备的浮点溢出模式，未定义的模式返回 `None`。
+    pub fn try_sat_mode(&self) -> Result<Option<SatMode>, AclError> {
+        let mut mode = aclrtFloatOverflowMode::ACL_RT_OVERFLOW_MODE_UNDEF;
+        try_acl!(aclrtGetDeviceSatMode(&mut mode))?;
+        Ok(match mode {
+            aclrtFloatOverflowMode::ACL_RT_OVERFLOW_MODE_SATURATION => Some(SatMode::Saturation),
+            aclrtFloatOverflowMode::ACL_RT_OVERFLOW_MODE_INFNAN => Some(SatMode::InfNan),
+            _ => None,
+        })
+    }
+
+    /// 设置算子等待事件的超时时间，对整个进程生效。
+    ///
+    /// 时间向上取整到秒，[`Duration::ZERO`] 表示永不超时。
+    #[inline]
+    pub fn set_op_wait_timeout(&self, timeout: Duration) {
+        self.try_set_op_wait_timeout(timeout).unwrap()
+    }
+
+    /// 设置算子等待事件的超时时间，对整个进程生效。
+    ///
+    /// 时间向上取整到秒，[`Duration::ZERO`] 表示永不超时。
+    #[inline]
+    pub fn try_set_op_wait_timeout(&self, timeout: Duration) -> Result<(), AclError> {
+        let secs = secs(timeout);
+        try_acl!(aclrtSetOpWaitTimeout(secs))?;
+        OP_WAIT_TIMEOUT.store(secs as _, Relaxed);
+        Ok(())
+    }
+
+    /// 通过本库设置的算子等待事件的超时时间，未设置时返回 `None`。
+    #[inline]
+    pub fn op_wait_timeout(&self) -> Option<Duration> {
+        load(&OP_WAIT_TIMEOUT)
+    }
+
+    /// 设置算子执行的超时时间，对整个进程生效。
+    ///
+    /// 时间向上取整到秒，[`Duration::ZERO`] 表示永不超时。
+    #[inline]
+    pub fn set_op_execute_timeout(&self, timeout: Duration) {
+        self.try_set_op_execute_timeout(timeout).unwrap()
+    }
+
+    /// 设置算子执行的超时时间，对整个进程生效。
+    ///
+    /// 时间向上取整到秒，[`Duration::ZERO`] 表示永不超时。
+    #[inline]
+    pub fn try_set_op_execute_timeout(&self, timeout: Duration) -> Result<(), AclError> {
+        let secs = secs(timeout);
+        try_acl!(aclrtSetOpExecuteTimeOut(secs))?;
+        OP_EXECUTE_TIMEOUT.store(secs as _, Relaxed);
+        Ok(())
+    }
+
+    /// 通过本库设置的算子执行的超时时间，未设置时返回 `None`。
+    #[inline]
+    pub fn op_execute_timeout(&self) -> Option<Duration> {
+        load(&OP_EXECUTE_TIMEOUT)
+    }
+}
+
+impl Context<'_> {
+    /// 在上下文上设置系统参数，见 [`CurrentCtx::set_sys_param`]。
+    #[inline]
+    pub fn set_sys_param(&self, param: SysParam, value: i64) {
+        self.apply(|ctx| ctx.set_sys_param(param, value))
+    }
+
+    /// 在上下文上设置系统参数，见 [`CurrentCtx::set_sys_param`]。
+    #[inline]
+    pub fn try_set_sys_param(&self, param: SysParam, value: i64) -> Result<(), AclError> {
+        self.try_push()?.try_set_sys_param(param, value)
+    }
+
+    /// 查询上下文的系统参数，见 [`CurrentCtx::sys_param`]。
+    #[inline]
+    pub fn sys_param(&self, param: SysParam) -> i64 {
+        self.apply(|ctx| ctx.sys_param(param))
+    }
+
+    /// 查询上下文的系统参数，见 [`CurrentCtx::sys_param`]。
+    #[inline]
+    pub fn try_sys_param(&self, param: SysParam) -> Result<i64, AclError> {
+        self.try_push()?.try_sys_param(param)
+    }
+
+    /// 开启或关闭上下文的确定性计算。
+    #[inline]
+    pub fn set_deterministic(&self, enable: bool) {
+        self.apply(|ctx| ctx.set_deterministic(enable))
+    }
+
+    /// 上下文是否开启确定性计算。
+    #[inline]
+    pub fn is_deterministic(&self) -> bool {
+        self.apply(|ctx| ctx.is_deterministic())
+    }
+
+    /// 设置上下文所在设备的浮点溢出模式，见 [`CurrentCtx::set_sat_mode`]。
+    #[inline]
+    pub fn set_sat_mode(&self, mode: SatMode) {
+        self.apply(|ctx| ctx.set_sat_mode(mode))
+    }
+
+    /// 设置上下文所在设备的浮点溢出模式，见 [`CurrentCtx::set_sat_mode`]。
+    #[inline]
+    pub fn try_set_sat_mode(&self, mode: SatMode) -> Result<(), AclError> {
+        self.try_push()?.try_set_sat_mode(mode)
+    }
+
+    /// 查询上下文所在设备的浮点溢出模式，见 [`CurrentCtx::sat_mode`]。
+    #[inline]
+    pub fn sat_mode(&self) -> Option<SatMode> {
+        self.apply(|ctx| ctx.sat_mode())
+    }
+
+    /// 查询上下文所在设备的浮点溢出模式，见 [`CurrentCtx::sat_mode`]。
+    #[inline]
+    pub fn try_sat_mode(&self) -> Result<Option<SatMode>, AclError> {
+        self.try_push()?.try_sat_mode()
+    }
+
+    /// 设置算子等待事件的超时时间，见 [`CurrentCtx::set_op_wait_timeout`]。
+    #[inline]
+    pub fn set_op_wait_timeout(&self, timeout: Duration) {
+        self.apply(|ctx| ctx.set_op_wait_timeout(timeout))
+    }
+
+    /// 设置算子等待事件的超时时间，见 [`CurrentCtx::set_op_wait_timeout`]。
+    #[inline]
+    pub fn try_set_op_wait_timeout(&self, timeout: Duration) -> Result<(), AclError> {
+        self.try_push()?.try_set_op_wait_timeout(timeout)
+    }
+
+    /// 通过本库设置的算子等待事件的超时时间，未设置时返回 `None`。
+    #[inline]
+    pub fn op_wait_timeout(&self) -> Option<Duration> {
+        load(&OP_WAIT_TIMEOUT)
+    }
+
+    /// 设置算子执行的超时时间，见 [`CurrentCtx::set_op_execute_timeout`]。
+    #[inline]
+    pub fn set_op_execute_timeout(&self, timeout: Duration) {
+        self.apply(|ctx| ctx.set_op_execute_timeout(timeout))
+    }
+
+    /// 设置算子执行的超时时间，见 [`CurrentCtx::set_op_execute_timeout`]。
+    #[inline]
+    pub fn try_set_op_execute_timeout(&self, timeout: Duration) -> Result<(), AclError> {
+        self.try_push()?.try_set_op_execute_timeout(timeout)
+    }
+
+    /// 通过本库设置的算子执行的超时时间，未设置时返回 `None`。
+    #[inline]
+    pub fn op_execute_timeout(&self) -> Option<Duration> {
+        load(&OP_EXECUTE_TIMEOUT)
+    }
+}
+
+// 运行时没有提供超时时间的查询接口，记录最后一次设置的值
+const UNSET: u64 = u64::MAX;
+static OP_WAIT_TIMEOUT: AtomicU64 = AtomicU64::new(UNSET);
+static OP_EXECUTE_TIMEOUT: AtomicU64 = AtomicU64::new(UNSET);
+
+/// 向上取整到秒，避免不足 1 秒的超时变成 0 即永不超时。
+#[inline]
+fn secs(timeout: Duration) -> u32 {
+    let secs = timeout
+        .as_secs()
+        .saturating_add((timeout.subsec_nanos() > 0) as _);
+    secs.try_into().unwrap_or(u32::MAX)
+}
+
+#[inline]
+fn load(timeout: &AtomicU64) -> Option<Duration> {
+    match timeout.load(Relaxed) {
+        UNSET => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
+#[test]
+fn test_secs() {
+    assert_eq!(secs(Duration::ZERO), 0);
+    assert_eq!(secs(Duration::from_millis(500)), 1);
+    assert_eq!(secs(Duration::from_secs(3)), 3);
+    assert_eq!(secs(Duration::from_millis(3001)), 4);
+    assert_eq!(secs(Duration::MAX), u32::MAX);
+}
+
+#[test]
+fn test() {
+    use crate::Device;
+
+    let acl = crate::Acl::init();
+    if Device::count(&acl) == 0 {
+        return;
+    }
+    let context = Device::new(&acl, 0).context();
+    context.set_deterministic(true);
+    assert!(context.is_deterministic());
+    context.set_op_execute_timeout(Duration::from_millis(500));
+    assert_eq!(context.op_execute_timeout(), Some(Duration::from_secs(1)));
+
+    context.apply(|ctx| {
+        ctx.set_deterministic(true);
+        assert!(ctx.is_deterministic());
+        ctx.set_deterministic(false);
+        assert!(!ctx.is_deterministic());
+
+        ctx.set_op_wait_timeout(Duration::from_secs(60));
+        assert_eq!(ctx.op_wait_timeout(), Some(Duration::from_secs(60)));
+        println!("sat mode: {:?}", ctx.sat_mode());
+    });
+}
@@ -0,0 +1,144 @@
+﻿use crate::{Acl, AclError, Context, Device};
+use context_spore::AsRaw;
+use std::{fmt, sync::Mutex};
+
+/// 已开启的设备间访问，`(dev, peer)` 表示 `dev` 可以访问 `peer` 的存储。
+///
+/// 运行时不提供查询接口，因此由开启和关闭的接口记录。
+static ENABLED: Mutex<Vec<(u32, u32)>> = Mutex::new(Vec::new());
+
+impl Device<'_> {
+    /// 设备是否能直接访问 `peer` 的存储。
+    #[inline]
+    pub fn can_access_peer(&self, peer: &Device) -> bool {
+        self.try_can_access_peer(peer).unwrap()
+    }
+
+    /// 设备是否能直接访问 `peer` 的存储。
+    #[inline]
+    pub fn try_can_access_peer(&self, peer: &Device) -> Result<bool, AclError> {
+        let (dev, peer) = unsafe { (self.as_raw(), peer.as_raw()) };
+        if dev == peer {
+            return Ok(true);
+        }
+        let mut ans = 0;
+        try_acl!(aclrtDeviceCanAccessPeer(&mut ans, dev as _, peer as _))?;
+        Ok(ans != 0)
+    }
+}
+
+impl Context<'_> {
+    /// 开启上下文所在设备对 `peer` 的存储的访问。
+    #[inline]
+    pub fn enable_peer_access(&self, peer: &Device) {
+        self.try_enable_peer_access(peer).unwrap()
+    }
+
+    /// 开启上下文所在设备对 `peer` 的存储的访问。
+    pub fn try_enable_peer_access(&self, peer: &Device) -> Result<(), AclError> {
+        let peer = unsafe { peer.as_raw() };
+        self.apply(|_| try_acl!(aclrtDeviceEnablePeerAccess(peer as _, 0)))?;
+        let dev = unsafe { self.device().as_raw() };
+        let mut enabled = ENABLED.lock().unwrap();
+        if !enabled.contains(&(dev, peer)) {
+            enabled.push((dev, peer))
+        }
+        Ok(())
+    }
+
+    /// 关闭上下文所在设备对 `peer` 的存储的访问。
+    #[inline]
+    pub fn disable_peer_access(&self, peer: &Device) {
+        self.try_disable_peer_access(peer).unwrap()
+    }
+
+    /// 关闭上下文所在设备对 `peer` 的存储的访问。
+    pub fn try_disable_peer_access(&self, peer: &Device) -> Result<(), AclError> {
+        let peer = unsafe { peer.as_raw() };
+        self.apply(|_| try_acl!(aclrtDeviceDisablePeerAccess(peer as _)))?;
+        let dev = unsafe { self.device().as_raw() };
+        ENABLED.lock().unwrap().retain(|&pair| pair != (dev, peer));
+        Ok(())
+    }
+
+    /// 上下文所在设备对 `peer` 的存储的访问是否已经通过本库开启。
+    pub fn is_peer_access_enabled(&self, peer: &Device) -> bool {
+        let (dev, peer) = unsafe { (self.device().as_raw(), peer.as_raw()) };
+        dev == peer || ENABLED.lock().unwrap().contains(&(dev, peer))
+    }
+}
+
+/// 所有设备之间能否直接访问的矩阵。
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Topology {
+    n: usize,
+    access: Vec<bool>,
+}
+
+impl Topology {
+    /// 探测所有设备之间的访问能力。
+    #[inline]
+    pub fn detect(acl: &Acl) -> Self {
+        Self::try_detect(acl).unwrap()
+    }
+
+    /// 探测所有设备之间的访问能力。
+    pub fn try_detect(acl: &Acl) -> Result<Self, AclError> {
+        let devices = Device::all(acl);
+        let mut access = Vec::with_capacity(devices.len() * devices.len());
+        for dev in &devices {
+            for peer in &devices {
+                access.push(dev.try_can_access_peer(peer)?)
+            }
+        }
+        Ok(Self {
+            n: devices.len(),
+            access,
+        })
+    }
+
+    /// 设备数量。
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.n
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// 逻辑设备 `dev` 能否直接访问逻辑设备 `peer` 的存储。
+    #[inline]
+    pub fn can_access(&self, dev: usize, peer: usize) -> bool {
+        assert!(dev < self.n && peer < self.n);
+        self.access[dev * self.n + peer]
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "     ")?;
+        for peer in 0..self.n {
+            write!(f, " {:>4}", format!("Dev{peer}"))?
+        }
+        writeln!(f)?;
+        for dev in 0..self.n {
+            write!(f, "{:>5}", format!("Dev{dev}"))?;
+            for peer in 0..self.n {
+                let mark = if self.can_access(dev, peer) { "Y" } else { "N" };
+                write!(f, " {mark:>4}")?
+            }
+            writeln!(f)?
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test() {
+    let acl = Acl::init();
+    let topology = Topology::detect(&acl);
+    assert_eq!(topology.len(), Device::count(&acl));
+    print!("{topology}");
+}
@@ -0,0 +1,132 @@
+﻿use crate::Device;
+use std::fmt;
+
+/// 昇腾 AI 处理器的型号，由 SoC 名称解析得到。
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SocVersion {
+    Ascend310,
+    /// Ascend310P1、Ascend310P3 等。
+    Ascend310P,
+    /// Ascend310B1 等。
+    Ascend310B,
+    /// Ascend910A、Ascend910ProB、Ascend910PremiumA 等。
+    Ascend910,
+    Ascend910B1,
+    Ascend910B2,
+    Ascend910B3,
+    Ascend910B4,
+    /// Ascend910_9391、Ascend910_9392 等。
+    Ascend910_93,
+    /// 无法识别的 SoC 名称。
+    Unknown(String),
+}
+
+impl Device<'_> {
+    /// 设备的 SoC 型号。
+    #[inline]
+    pub fn soc_version(&self) -> SocVersion {
+        self.name().to_string_lossy().as_ref().into()
+    }
+}
+
+impl From<&str> for SocVersion {
+    fn from(name: &str) -> Self {
+        let Some(model) = name.strip_prefix("Ascend") else {
+            return Self::Unknown(name.into());
+        };
+        if let Some(suffix) = model.strip_prefix("910B") {
+            // Ascend910B2C 与 Ascend910B2 属于同一档位
+            match suffix.chars().next() {
+                Some('1') => return Self::Ascend910B1,
+                Some('2') => return Self::Ascend910B2,
+                Some('3') => return Self::Ascend910B3,
+                Some('4') => return Self::Ascend910B4,
+                _ => {}
+            }
+        }
+        if model.starts_with("910_93") {
+            Self::Ascend910_93
+        } else if model.starts_with("910") && !model.starts_with("910B") {
+            Self::Ascend910
+        } else if model.starts_with("310P") {
+            Self::Ascend310P
+        } else if model.starts_with("310B") {
+            Self::Ascend310B
+        } else if model == "310" {
+            Self::Ascend310
+        } else {
+            Self::Unknown(name.into())
+        }
+    }
+}
+
+impl SocVersion {
+    /// 是否支持 bf16 计算。
+    pub fn supports_bf16(&self) -> bool {
+        matches!(
+            self,
+            Self::Ascend910B1
+                | Self::Ascend910B2
+                | Self::Ascend910B3
+                | Self::Ascend910B4
+                | Self::Ascend910_93
+        )
+    }
+
+    /// 是否具有独立的 Vector Core。
+    pub fn has_vector_cores(&self) -> bool {
+        matches!(
+            self,
+            Self::Ascend310P
+                | Self::Ascend910B1
+                | Self::Ascend910B2
+                | Self::Ascend910B3
+                | Self::Ascend910B4
+                | Self::Ascend910_93
+        )
+    }
+}
+
+impl fmt::Display for SocVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(name) => f.write_str(name),
+            known => write!(f, "{known:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_parse() {
+    use SocVersion::*;
+
+    for (name, soc) in [
+        ("Ascend310", Ascend310),
+        ("Ascend310P1", Ascend310P),
+        ("Ascend310P3", Ascend310P),
+        ("Ascend310B1", Ascend310B),
+        ("Ascend910A", Ascend910),
+        ("Ascend910ProB", Ascend910),
+        ("Ascend910PremiumA", Ascend910),
+        ("Ascend910B1", Ascend910B1),
+        ("Ascend910B2", Ascend910B2),
+        ("Ascend910B2C", Ascend910B2),
+        ("Ascend910B3", Ascend910B3),
+        ("Ascend910B4", Ascend910B4),
+        ("Ascend910_9391", Ascend910_93),
+        ("Ascend910B", Unknown("Ascend910B".into())),
+        ("Ascend920", Unknown("Ascend920".into())),
+        ("", Unknown("".into())),
+    ] {
+        assert_eq!(SocVersion::from(name), soc, "{name}");
+    }
+
+    assert!(Ascend910B3.supports_bf16());
+    assert!(!Ascend310P.supports_bf16());
+    assert!(!Ascend910.supports_bf16());
+    assert!(Ascend310P.has_vector_cores());
+    assert!(!Ascend910.has_vector_cores());
+    assert!(!Unknown("Ascend920".into()).has_vector_cores());
+    assert_eq!(Ascend910B4.to_string(), "Ascend910B4");
+    assert_eq!(Unknown("X".into()).to_string(), "X");
+}
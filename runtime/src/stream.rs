@@ -1,6 +1,7 @@
 ﻿use crate::{
-    bindings::{aclrtStream, aclrtStreamStatus::*},
-    CurrentCtx,
+    bindings::{aclrtStream, aclrtStreamStatus::*, ACL_STREAM_FAST_LAUNCH, ACL_STREAM_FAST_SYNC},
+    leak::{report_release, ResourceKind},
+    AclError, CurrentCtx,
 };
 use context_spore::{impl_spore, AsRaw};
 use std::{marker::PhantomData, ptr::null_mut};
@@ -10,17 +11,87 @@ impl_spore!(Stream and StreamSpore by (CurrentCtx, aclrtStream));
 impl CurrentCtx {
     #[inline]
     pub fn stream(&self) -> Stream {
+        self.try_stream().unwrap()
+    }
+
+    #[inline]
+    pub fn try_stream(&self) -> Result<Stream<'_>, AclError> {
         let mut stream = null_mut();
-        acl!(aclrtCreateStream(&mut stream));
-        Stream(unsafe { self.wrap_raw(stream) }, PhantomData)
+        try_acl!(aclrtCreateStream(&mut stream))?;
+        Ok(Stream(unsafe { self.wrap_raw(stream) }, PhantomData))
+    }
+}
+
+/// 按指定的优先级和选项创建流。
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StreamBuilder {
+    priority: u32,
+    flags: u32,
+}
+
+impl StreamBuilder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 流的优先级，数值越小优先级越高，默认为 0。
+    #[inline]
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 快速下发任务。
+    #[inline]
+    pub fn fast_launch(self, enable: bool) -> Self {
+        self.flag(ACL_STREAM_FAST_LAUNCH, enable)
+    }
+
+    /// 快速同步流。
+    #[inline]
+    pub fn fast_sync(self, enable: bool) -> Self {
+        self.flag(ACL_STREAM_FAST_SYNC, enable)
+    }
+
+    #[inline]
+    fn flag(mut self, flag: u32, enable: bool) -> Self {
+        if enable {
+            self.flags |= flag
+        } else {
+            self.flags &= !flag
+        }
+        self
+    }
+
+    #[inline]
+    pub fn build(self, ctx: &CurrentCtx) -> Stream<'_> {
+        self.try_build(ctx).unwrap()
+    }
+
+    #[inline]
+    pub fn try_build(self, ctx: &CurrentCtx) -> Result<Stream<'_>, AclError> {
+        let mut stream = null_mut();
+        try_acl!(aclrtCreateStreamWithConfig(
+            &mut stream,
+            self.priority,
+            self.flags
+        ))?;
+        Ok(Stream(unsafe { ctx.wrap_raw(stream) }, PhantomData))
     }
 }
 
 impl Drop for Stream<'_> {
     #[inline]
     fn drop(&mut self) {
-        self.synchronize();
-        acl!(aclrtDestroyStream(self.0.rss));
+        if let Err(e) = self.try_synchronize() {
+            log::error!("failed to synchronize stream before destroying: {e}")
+        }
+        crate::callback::unsubscribe(self.0.ctx, self.0.rss);
+        report_release(
+            ResourceKind::Stream,
+            try_acl!(aclrtDestroyStream(self.0.rss)),
+        )
     }
 }
 
@@ -35,14 +106,50 @@ impl AsRaw for Stream<'_> {
 impl Stream<'_> {
     #[inline]
     pub fn synchronize(&self) {
-        acl!(aclrtSynchronizeStream(self.0.rss));
+        self.try_synchronize().unwrap()
+    }
+
+    #[inline]
+    pub fn try_synchronize(&self) -> Result<(), AclError> {
+        try_acl!(aclrtSynchronizeStream(self.0.rss))
     }
 
     #[inline]
     pub fn is_complete(&self) -> bool {
+        self.try_is_complete().unwrap()
+    }
+
+    #[inline]
+    pub fn try_is_complete(&self) -> Result<bool, AclError> {
         let mut status = ACL_STREAM_STATUS_RESERVED;
-        acl!(aclrtStreamQuery(self.0.rss, &mut status));
+        try_acl!(aclrtStreamQuery(self.0.rss, &mut status))?;
         assert_ne!(status, ACL_STREAM_STATUS_RESERVED);
-        status == ACL_STREAM_STATUS_COMPLETE
+        Ok(status == ACL_STREAM_STATUS_COMPLETE)
     }
 }
+
+#[test]
+fn test_builder() {
+    use crate::{memcpy_d2h, Device};
+
+    let acl = crate::Acl::init();
+    if Device::count(&acl) == 0 {
+        return;
+    }
+    Device::new(&acl, 0).context().apply(|ctx| {
+        let stream = StreamBuilder::new()
+            .priority(1)
+            .fast_launch(true)
+            .fast_sync(true)
+            .build(ctx);
+        let data = (0..1024u32).collect::<Vec<_>>();
+        let mut dev = ctx.malloc::<u32>(data.len());
+        stream.memcpy_h2d(&mut dev, &data);
+        stream.synchronize();
+        assert!(stream.is_complete());
+
+        let mut host = vec![0u32; data.len()];
+        memcpy_d2h(&mut host, &dev);
+        assert_eq!(host, data)
+    });
+}
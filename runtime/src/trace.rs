@@ -0,0 +1,325 @@
+﻿use crate::{
+    bindings::{aclrtContext, aclrtStream},
+    json::{array, object, string},
+    AclError, CurrentCtx, Event, EventSpore, Stream,
+};
+use context_spore::{AsRaw, ContextResource, ContextSpore};
+use std::{cell::RefCell, fs, io, mem::take, path::Path, sync::OnceLock, time::Instant};
+
+/// 收集命名区间的记录器，在 [`Recorder::record`] 中调用的 [`Stream::range`] 记录到这个记录器。
+///
+/// 区间的事件属于记录器，记录器释放时一并释放。
+/// 记录器创建时用一个事件对齐设备时间和主机时间，因此不同记录器收集的时间线可以合并。
+pub struct Recorder<'ctx> {
+    ctx: &'ctx CurrentCtx,
+    /// 对齐用的事件，以及它完成时的主机时间。
+    anchor: (Event<'ctx>, f64),
+    ranges: RefCell<Vec<Range>>,
+}
+
+/// 流上一段命名区间的起止事件。
+struct Range {
+    name: String,
+    stream: aclrtStream,
+    begin: EventSpore,
+    end: EventSpore,
+}
+
+/// 一次 [`Recorder::record`] 中记录的区间。
+struct Recording {
+    ctx: aclrtContext,
+    ranges: Vec<Range>,
+}
+
+thread_local! {
+    /// 本线程正在进行的 [`Recorder::record`]，内层在后。
+    static RECORDING: RefCell<Vec<Recording>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 进程内所有时间线共同的主机时间，单位微秒。
+fn host_us() -> f64 {
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    ORIGIN.get_or_init(Instant::now).elapsed().as_secs_f64() * 1e6
+}
+
+impl CurrentCtx {
+    /// 创建记录当前上下文上命名区间的记录器。
+    #[inline]
+    pub fn recorder(&self) -> Recorder<'_> {
+        self.try_recorder().unwrap()
+    }
+
+    /// 创建记录当前上下文上命名区间的记录器。
+    pub fn try_recorder(&self) -> Result<Recorder<'_>, AclError> {
+        // 在空闲的流上记录事件，同步返回时的主机时间近似为事件完成的时间
+        let stream = self.try_stream()?;
+        let anchor = stream.try_record()?;
+        anchor.try_synchronize()?;
+        let host = host_us();
+        Ok(Recorder {
+            ctx: self,
+            anchor: (anchor, host),
+            ranges: RefCell::new(Vec::new()),
+        })
+    }
+}
+
+impl Stream<'_> {
+    /// 用起止事件标记 `f` 提交到流上的任务。
+    ///
+    /// 只在同一上下文的 [`Recorder::record`] 中记录，否则直接执行 `f`。
+    #[inline]
+    pub fn range<T>(&self, name: impl Into<String>, f: impl FnOnce(&Self) -> T) -> T {
+        self.try_range(name, f).unwrap()
+    }
+
+    /// 用起止事件标记 `f` 提交到流上的任务。
+    ///
+    /// 只在同一上下文的 [`Recorder::record`] 中记录，否则直接执行 `f`。
+    pub fn try_range<T>(
+        &self,
+        name: impl Into<String>,
+        f: impl FnOnce(&Self) -> T,
+    ) -> Result<T, AclError> {
+        let ctx = unsafe { self.ctx().as_raw() };
+        if !RECORDING.with_borrow(|r| r.iter().any(|r| r.ctx == ctx)) {
+            return Ok(f(self));
+        }
+        let begin = self.try_record()?;
+        let ans = f(self);
+        let end = self.try_record()?;
+        let range = Range {
+            name: name.into(),
+            stream: unsafe { self.as_raw() },
+            begin: begin.sporulate(),
+            end: end.sporulate(),
+        };
+        RECORDING.with_borrow_mut(|r| {
+            let recording = r.iter_mut().rev().find(|r| r.ctx == ctx).unwrap();
+            recording.ranges.push(range)
+        });
+        Ok(ans)
+    }
+}
+
+impl Recorder<'_> {
+    /// 执行 `f`，其中调用的 [`Stream::range`] 记录到这个记录器。
+    pub fn record<T>(&self, f: impl FnOnce() -> T) -> T {
+        /// 即使 `f` 发生 panic，也将区间移入记录器以释放事件。
+        struct Guard<'a>(&'a RefCell<Vec<Range>>);
+
+        impl Drop for Guard<'_> {
+            fn drop(&mut self) {
+                let recording = RECORDING.with_borrow_mut(Vec::pop).unwrap();
+                self.0.borrow_mut().extend(recording.ranges)
+            }
+        }
+
+        RECORDING.with_borrow_mut(|r| {
+            r.push(Recording {
+                ctx: unsafe { self.ctx.as_raw() },
+                ranges: Vec::new(),
+            })
+        });
+        let _guard = Guard(&self.ranges);
+        f()
+    }
+
+    /// 等待所有区间完成，收集成时间线并释放事件。
+    #[inline]
+    pub fn timeline(self) -> Timeline {
+        self.try_timeline().unwrap()
+    }
+
+    /// 等待所有区间完成，收集成时间线并释放事件。
+    pub fn try_timeline(mut self) -> Result<Timeline, AclError> {
+        let ctx = self.ctx;
+        let ranges = take(self.ranges.get_mut())
+            .into_iter()
+            .map(|r| (r.name, r.stream, r.begin.sprout(ctx), r.end.sprout(ctx)))
+            .collect::<Vec<_>>();
+        for (.., end) in &ranges {
+            end.try_synchronize()?
+        }
+
+        let (anchor, host) = &self.anchor;
+        let pid = unsafe { ctx.try_dev()?.as_raw() };
+        let mut streams = Vec::new();
+        let mut timeline = Timeline::new();
+        for (name, stream, begin, end) in ranges {
+            let tid = match streams.iter().position(|&s| s == stream) {
+                Some(i) => i,
+                None => {
+                    streams.push(stream);
+                    streams.len() - 1
+                }
+            };
+            timeline.push(Span {
+                name,
+                pid,
+                tid: tid as _,
+                begin: host + elapsed_us(anchor, &begin)?,
+                end: host + elapsed_us(anchor, &end)?,
+            })
+        }
+        Ok(timeline)
+    }
+}
+
+impl Drop for Recorder<'_> {
+    fn drop(&mut self) {
+        for range in take(self.ranges.get_mut()) {
+            drop(range.begin.sprout(self.ctx));
+            drop(range.end.sprout(self.ctx))
+        }
+    }
+}
+
+/// 两个事件之间的微秒数，可以为负。
+fn elapsed_us(start: &Event, end: &Event) -> Result<f64, AclError> {
+    let mut ms = 0.0f32;
+    try_acl!(aclrtEventElapsedTime(&mut ms, start.as_raw(), end.as_raw()))?;
+    Ok(ms as f64 * 1e3)
+}
+
+/// 时间线上的一段区间。
+#[derive(Clone, PartialEq, Debug)]
+pub struct Span {
+    pub name: String,
+    /// 设备序号。
+    pub pid: u32,
+    /// 流在上下文中的序号。
+    pub tid: u32,
+    /// 开始时间，单位微秒，零点为进程内共同的主机时间。
+    pub begin: f64,
+    /// 结束时间，单位微秒，零点为进程内共同的主机时间。
+    pub end: f64,
+}
+
+/// 命名区间组成的时间线，可以导出为 Chrome trace_event 格式。
+#[derive(Clone, Default, Debug)]
+pub struct Timeline {
+    spans: Vec<Span>,
+}
+
+impl Timeline {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn push(&mut self, span: Span) {
+        self.spans.push(span)
+    }
+
+    /// 合并另一个时间线，例如其他设备上收集的时间线。
+    ///
+    /// 记录器在创建时对齐设备时间和主机时间，因此合并的时间线大致对齐，误差在一次同步的延迟以内。
+    #[inline]
+    pub fn extend(&mut self, other: Self) {
+        self.spans.extend(other.spans)
+    }
+
+    #[inline]
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// 平移时间使最早的区间从 0 开始，合并所有时间线之后调用。
+    pub fn normalize(&mut self) {
+        let min = self
+            .spans
+            .iter()
+            .map(|s| s.begin)
+            .fold(f64::INFINITY, f64::min);
+        if min.is_finite() {
+            for span in &mut self.spans {
+                span.begin -= min;
+                span.end -= min
+            }
+        }
+    }
+
+    /// 生成 Chrome trace_event 格式的 json 文本。
+    pub fn to_json(&self) -> String {
+        let events = self.spans.iter().map(|s| {
+            object(vec![
+                ("name", string(&s.name)),
+                ("ph", string("X")),
+                ("pid", s.pid.to_string()),
+                ("tid", s.tid.to_string()),
+                ("ts", format!("{:.3}", s.begin)),
+                ("dur", format!("{:.3}", s.end - s.begin)),
+            ])
+        });
+        object(vec![("traceEvents", array(events))])
+    }
+
+    /// 将 trace_event 格式的 json 写入文件，可以在 `chrome://tracing` 或 Perfetto 中查看。
+    #[inline]
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+}
+
+#[test]
+fn test_json() {
+    let mut timeline = Timeline::new();
+    timeline.push(Span {
+        name: "attention".into(),
+        pid: 0,
+        tid: 0,
+        begin: 12.5,
+        end: 40.,
+    });
+    timeline.push(Span {
+        name: "\"mlp\"".into(),
+        pid: 0,
+        tid: 1,
+        begin: 2.5,
+        end: 3.25,
+    });
+    timeline.normalize();
+    assert_eq!(timeline.spans()[1].begin, 0.);
+    assert_eq!(
+        timeline.to_json(),
+        r#"{"traceEvents":[{"name":"attention","ph":"X","pid":0,"tid":0,"ts":10.000,"dur":27.500},{"name":"\"mlp\"","ph":"X","pid":0,"tid":1,"ts":0.000,"dur":0.750}]}"#
+    );
+}
+
+#[test]
+fn test_recorder() {
+    use crate::Device;
+
+    let acl = crate::Acl::init();
+    if Device::count(&acl) == 0 {
+        return;
+    }
+    Device::new(&acl, 0).context().apply(|ctx| {
+        let recorder = ctx.recorder();
+        let a = ctx.stream();
+        let b = ctx.stream();
+        let data = vec![0u8; 1 << 20];
+        let mut dev = ctx.malloc::<u8>(data.len());
+        // 不在记录器中时不记录
+        a.range("ignored", |_| ());
+        recorder.record(|| {
+            a.range("h2d", |s| s.memcpy_h2d(&mut dev, &data));
+            b.range("sync", |s| s.wait_for(&a.record()))
+        });
+        // 后创建的记录器与前一个对齐
+        let other = ctx.recorder();
+        other.record(|| a.range("h2d", |s| s.memcpy_h2d(&mut dev, &data)));
+
+        let mut timeline = recorder.timeline();
+        timeline.extend(other.timeline());
+        let spans = timeline.spans();
+        assert_eq!(spans.len(), 3);
+        assert_eq!((spans[0].tid, spans[1].tid), (0, 1));
+        assert!(spans.iter().all(|s| s.begin >= 0. && s.end >= s.begin));
+        assert!(spans[2].begin >= spans[0].begin);
+        timeline.normalize();
+        println!("{}", timeline.to_json())
+    });
+}
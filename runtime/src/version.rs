@@ -0,0 +1,227 @@
+﻿use crate::AclError;
+use std::{cmp::Ordering, fmt, fs, str::FromStr};
+
+/// `aclrtGetVersion` 报告的 AscendCL 接口版本。
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct RuntimeVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+/// CANN 软件包的版本，形如 `8.0.RC2`、`8.0.0` 或 `8.0.RC2.alpha003`。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ToolkitVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub release: Release,
+    /// 预发布的 alpha 版本号。
+    pub alpha: Option<u32>,
+}
+
+/// CANN 版本的发布阶段，同一版本的 RC 总是早于正式发布。
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Release {
+    /// 候选版本，如 `RC2`。
+    Rc(u32),
+    /// 正式版本的补丁号，如 `8.0.0` 中的 `0`。
+    Patch(u32),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseVersionError(String);
+
+#[inline]
+pub fn version() -> RuntimeVersion {
+    try_version().unwrap()
+}
+
+#[inline]
+pub fn try_version() -> Result<RuntimeVersion, AclError> {
+    let mut ans = (0, 0, 0);
+    try_acl!(aclrtGetVersion(&mut ans.0, &mut ans.1, &mut ans.2))?;
+    Ok(RuntimeVersion {
+        major: ans.0 as _,
+        minor: ans.1 as _,
+        patch: ans.2 as _,
+    })
+}
+
+impl ToolkitVersion {
+    /// 读取已安装的 CANN toolkit 的版本。
+    ///
+    /// 找不到 toolkit 或无法识别 `version.cfg` 时返回 `None`。
+    pub fn installed() -> Option<Self> {
+        let home = search_ascend_tools::find_ascend_toolkit_home()?;
+        let cfg = fs::read_to_string(home.join("version.cfg")).ok()?;
+        Self::from_version_cfg(&cfg)
+    }
+
+    /// 从 `version.cfg` 的内容中解析版本，优先采用 runtime 的版本。
+    ///
+    /// 识别形如 `runtime_running_version=[7.0.0.5.242:8.0.RC2]` 或 `version=8.0.RC2` 的行，
+    /// 方括号中取最后一个版本。
+    pub fn from_version_cfg(cfg: &str) -> Option<Self> {
+        let mut first = None;
+        for line in cfg.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().trim_start_matches('[').trim_end_matches(']');
+            let value = value.rsplit(':').next().unwrap().trim();
+            let Ok(version) = value.parse() else {
+                continue;
+            };
+            if key.contains("runtime") {
+                return Some(version);
+            }
+            first.get_or_insert(version);
+        }
+        first
+    }
+}
+
+impl Ord for ToolkitVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.release)
+            .cmp(&(other.major, other.minor, other.release))
+            .then(match (self.alpha, other.alpha) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(&b),
+            })
+    }
+}
+
+impl PartialOrd for ToolkitVersion {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for RuntimeVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl fmt::Display for ToolkitVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.", self.major, self.minor)?;
+        match self.release {
+            Release::Rc(n) => write!(f, "RC{n}")?,
+            Release::Patch(n) => write!(f, "{n}")?,
+        }
+        if let Some(n) = self.alpha {
+            write!(f, ".alpha{n:03}")?
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RuntimeVersion {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseVersionError(s.into());
+        let mut parts = s.trim().split('.').map(|p| p.parse().map_err(|_| err()));
+        let mut next = || parts.next().unwrap_or_else(|| Err(err()));
+        let ans = Self {
+            major: next()?,
+            minor: next()?,
+            patch: next()?,
+        };
+        match parts.next() {
+            None => Ok(ans),
+            Some(_) => Err(err()),
+        }
+    }
+}
+
+impl FromStr for ToolkitVersion {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseVersionError(s.into());
+        let mut parts = s.trim().split('.');
+        let mut next = || parts.next().ok_or_else(err);
+        let major = next()?.parse().map_err(|_| err())?;
+        let minor = next()?.parse().map_err(|_| err())?;
+        let release = next()?;
+        let release = match release.strip_prefix("RC") {
+            Some(n) => Release::Rc(n.parse().map_err(|_| err())?),
+            None => Release::Patch(release.parse().map_err(|_| err())?),
+        };
+        let alpha = match parts.next() {
+            Some(alpha) => Some(
+                alpha
+                    .strip_prefix("alpha")
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(err)?,
+            ),
+            None => None,
+        };
+        match parts.next() {
+            None => Ok(Self {
+                major,
+                minor,
+                release,
+                alpha,
+            }),
+            Some(_) => Err(err()),
+        }
+    }
+}
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid version: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+#[test]
+fn test_parse() {
+    let v = |s: &str| s.parse::<ToolkitVersion>().unwrap();
+
+    for s in ["8.0.RC2", "8.0.0", "8.0.RC2.alpha003", "7.0.RC1"] {
+        assert_eq!(v(s).to_string(), s);
+    }
+    assert!(v("7.0.0") < v("8.0.RC1"));
+    assert!(v("8.0.RC1") < v("8.0.RC2.alpha003"));
+    assert!(v("8.0.RC2.alpha003") < v("8.0.RC2"));
+    assert!(v("8.0.RC3") < v("8.0.0"));
+    assert!("8.0".parse::<ToolkitVersion>().is_err());
+    assert!("8.0.RC2.beta1".parse::<ToolkitVersion>().is_err());
+
+    let rt = "1.0.12".parse::<RuntimeVersion>().unwrap();
+    assert_eq!(rt.to_string(), "1.0.12");
+    assert!(rt > "1.0.9".parse().unwrap());
+    assert!("1.0".parse::<RuntimeVersion>().is_err());
+    assert!("1.0.0.0".parse::<RuntimeVersion>().is_err());
+}
+
+#[test]
+fn test_version_cfg() {
+    let cfg = "\
+# version: 1.0
+compiler_version=[8.0.RC1:8.0.RC1]
+runtime_running_version=[7.0.0.5.242:8.0.RC2]
+";
+    assert_eq!(
+        ToolkitVersion::from_version_cfg(cfg),
+        Some("8.0.RC2".parse().unwrap())
+    );
+    assert_eq!(
+        ToolkitVersion::from_version_cfg("Version=8.0.0\n"),
+        Some("8.0.0".parse().unwrap())
+    );
+    assert_eq!(ToolkitVersion::from_version_cfg("# empty\n"), None);
+}